@@ -0,0 +1,430 @@
+//! Bevy integration for cybird.
+//!
+//! [`CybirdPlugin`] owns a plugin context as the [`CybirdContext`] resource,
+//! loads plugins from the configured sources at startup, collects their
+//! assets into [`CybirdAssets`] and reports what happened through
+//! [`PluginLoaded`], [`PluginFailed`] and [`PluginUnloaded`] messages. Every
+//! frame it runs the plugins' update hooks, reporting failures as
+//! [`PluginUpdateFailed`].
+
+use bevy_app::{App, Startup, Update};
+use bevy_ecs::prelude::*;
+use bevy_time::Time;
+use cybird::Context;
+use cybird::asset::AssetRegistry;
+use cybird::loader::{BoxedPlugin, Loader};
+use cybird::source::{NativeSource, PluginSource};
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub mod prelude {
+    pub use crate::{
+        CybirdAssets, CybirdContext, CybirdPlugin, LoadPlugins, PluginFailed, PluginLoaded,
+        PluginUnloaded, PluginUpdateFailed, UnloadPlugin, UpdatePlugins,
+    };
+}
+
+/// Adds a cybird context of type `C` to a Bevy app.
+///
+/// Plugins are opened from [`PluginSource`]s when the app starts. Paths given
+/// to [`CybirdPlugin::with_path`] may point at native plugin libraries or at
+/// directories, which are searched for libraries.
+pub struct CybirdPlugin<C: Context> {
+    sources: Mutex<Vec<BoxedSource<C>>>,
+    plugins: Mutex<Vec<BoxedPlugin<C>>>,
+}
+
+type BoxedSource<C> = Box<dyn PluginSource<C> + Send + Sync>;
+
+impl<C: Context> Default for CybirdPlugin<C> {
+    fn default() -> Self {
+        Self {
+            sources: Mutex::new(Vec::new()),
+            plugins: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<C: Context> CybirdPlugin<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a [`NativeSource`] for `path`.
+    ///
+    /// # Safety
+    ///
+    /// Every library found is opened, so the requirements of
+    /// [`NativeSource::new`] apply to each of them.
+    pub unsafe fn with_path(self, path: impl Into<PathBuf>) -> Self {
+        // SAFETY: upheld by the caller
+        self.with_source(unsafe { NativeSource::new(path) })
+    }
+
+    pub fn with_source<S>(self, source: S) -> Self
+    where
+        S: PluginSource<C> + Send + Sync + 'static,
+    {
+        self.sources.lock().unwrap().push(Box::new(source));
+        self
+    }
+
+    pub fn with_plugin<P>(self, plugin: P) -> Self
+    where
+        P: cybird::Plugin<C> + Send + Sync + 'static,
+    {
+        self.plugins.lock().unwrap().push(Box::new(plugin));
+        self
+    }
+}
+
+impl<C> bevy_app::Plugin for CybirdPlugin<C>
+where
+    C: Context + Default + Clone + Send + Sync + 'static,
+{
+    fn build(&self, app: &mut App) {
+        let mut loader = Loader::new();
+        for plugin in self.plugins.lock().unwrap().drain(..) {
+            loader.add_boxed(plugin);
+        }
+
+        app.insert_resource(CybirdContext {
+            context: C::default(),
+            loader,
+            sources: self.sources.lock().unwrap().drain(..).collect(),
+        })
+        .init_resource::<CybirdAssets>()
+        .add_message::<PluginLoaded>()
+        .add_message::<PluginFailed>()
+        .add_message::<PluginUnloaded>()
+        .add_message::<PluginUpdateFailed>()
+        .add_message::<UnloadPlugin>()
+        .add_systems(Startup, load_plugins::<C>.in_set(LoadPlugins))
+        .add_systems(Update, update_plugins::<C>.in_set(UpdatePlugins))
+        .add_systems(Update, unload_plugins::<C>.after(UpdatePlugins));
+    }
+}
+
+/// The startup system set that loads plugins. Order systems that read plugin
+/// registrations at startup after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LoadPlugins;
+
+/// The update system set that runs every plugin's update hook. Needs Bevy's
+/// `Time` resource, which `TimePlugin` provides.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UpdatePlugins;
+
+/// The plugin context shared by every system, and the loader that keeps its
+/// plugins, and with them their libraries, alive. Derefs to the context.
+///
+/// The two share a resource because Bevy drops resources in no particular
+/// order, while items in the context may point into native libraries. Fields
+/// drop in declaration order, so the context always goes first.
+#[derive(Resource)]
+pub struct CybirdContext<C: Context + Send + Sync + 'static> {
+    pub context: C,
+    pub loader: Loader<C>,
+    sources: Vec<BoxedSource<C>>,
+}
+
+impl<C: Context + Send + Sync + 'static> Deref for CybirdContext<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+impl<C: Context + Send + Sync + 'static> DerefMut for CybirdContext<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.context
+    }
+}
+
+/// The assets of every plugin that loaded at startup. A plugin's assets are
+/// removed when it is unloaded.
+#[derive(Resource, Default)]
+pub struct CybirdAssets(pub AssetRegistry);
+
+impl Deref for CybirdAssets {
+    type Target = AssetRegistry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct PluginLoaded {
+    pub id: String,
+    pub name: String,
+    pub author: String,
+    pub version: String,
+    pub description: Option<String>,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct PluginFailed {
+    /// The plugin id, or its origin when the plugin could not be opened.
+    pub source: String,
+    pub error: String,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct PluginUpdateFailed {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Message, Debug, Clone)]
+pub struct PluginUnloaded {
+    pub id: String,
+}
+
+/// Asks for the plugin with the given id to be unloaded, removing its
+/// registrations from the context and its assets from [`CybirdAssets`].
+///
+/// The request fails with a [`PluginFailed`] message if the context does not
+/// record owners, see [`Loader::unload`].
+#[derive(Message, Debug, Clone)]
+pub struct UnloadPlugin(pub String);
+
+fn load_plugins<C>(
+    mut cybird: ResMut<CybirdContext<C>>,
+    mut assets: ResMut<CybirdAssets>,
+    mut loaded: MessageWriter<PluginLoaded>,
+    mut failed: MessageWriter<PluginFailed>,
+) where
+    C: Context + Send + Sync + 'static,
+{
+    let CybirdContext {
+        context,
+        loader,
+        sources,
+    } = &mut *cybird;
+
+    for source in sources.iter() {
+        for error in loader.add_source(source.as_ref()) {
+            failed.write(PluginFailed {
+                source: error.origin.to_string(),
+                error: error.error.to_string(),
+            });
+        }
+    }
+
+    let result = loader.load_each(context, |plugin, result| match result {
+        Ok(()) => {
+            let metadata = plugin.metadata();
+            loaded.write(PluginLoaded {
+                id: plugin.id().to_string(),
+                name: metadata
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| plugin.id().to_string()),
+                author: plugin.author().to_string(),
+                version: plugin.version().to_string(),
+                description: metadata.description.clone(),
+            });
+        }
+        Err(error) => {
+            failed.write(PluginFailed {
+                source: plugin.id().to_string(),
+                error: error.to_string(),
+            });
+        }
+    });
+
+    if let Err(error) = result {
+        failed.write(PluginFailed {
+            source: "cybird".to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    for error in loader.load_assets(&mut assets.0) {
+        failed.write(PluginFailed {
+            source: "cybird".to_string(),
+            error,
+        });
+    }
+}
+
+fn update_plugins<C>(
+    mut cybird: ResMut<CybirdContext<C>>,
+    time: Res<Time>,
+    mut failed: MessageWriter<PluginUpdateFailed>,
+) where
+    C: Context + Send + Sync + 'static,
+{
+    let CybirdContext {
+        context, loader, ..
+    } = &mut *cybird;
+    for error in loader.update(context, time.delta()) {
+        failed.write(PluginUpdateFailed {
+            id: error.id,
+            error: error.error.to_string(),
+        });
+    }
+}
+
+fn unload_plugins<C>(
+    mut cybird: ResMut<CybirdContext<C>>,
+    mut assets: ResMut<CybirdAssets>,
+    mut requests: MessageReader<UnloadPlugin>,
+    mut unloaded: MessageWriter<PluginUnloaded>,
+    mut failed: MessageWriter<PluginFailed>,
+) where
+    C: Context + Send + Sync + 'static,
+{
+    let CybirdContext {
+        context, loader, ..
+    } = &mut *cybird;
+    for UnloadPlugin(id) in requests.read() {
+        match loader.unload(context, id) {
+            Ok(_) => {
+                assets.0.remove_plugin(id);
+                unloaded.write(PluginUnloaded { id: id.clone() });
+            }
+            Err(error) => {
+                failed.write(PluginFailed {
+                    source: id.clone(),
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cybird::Result;
+    use cybird::prelude::*;
+    use std::time::Duration;
+
+    #[derive(Default, Clone, Context)]
+    struct TestContext(Vec<Registrable>, Owners);
+
+    #[derive(Clone)]
+    enum Registrable {
+        Item(Item),
+    }
+
+    #[derive(Clone, Registrable)]
+    struct Item(String);
+
+    impl TestContext {
+        fn names(&self) -> Vec<&str> {
+            self.get_registrables::<Item>()
+                .into_iter()
+                .map(|Item(name)| name.as_str())
+                .collect()
+        }
+    }
+
+    /// Registers an item named after itself, and fails every update if asked.
+    struct TestPlugin {
+        id: &'static str,
+        fail_updates: bool,
+    }
+
+    impl Plugin<TestContext> for TestPlugin {
+        fn author(&self) -> &str {
+            "cybird"
+        }
+
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn load(&self, ctx: &mut TestContext) -> Result<()> {
+            ctx.register(Item(self.id.to_string()));
+            Ok(())
+        }
+
+        fn update(&mut self, _ctx: &mut TestContext, _dt: Duration) -> Result<()> {
+            if self.fail_updates {
+                Err("out of order".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(
+            CybirdPlugin::<TestContext>::new()
+                .with_plugin(TestPlugin {
+                    id: "a",
+                    fail_updates: false,
+                })
+                .with_plugin(TestPlugin {
+                    id: "b",
+                    fail_updates: true,
+                }),
+        )
+        .init_resource::<Time>();
+        app
+    }
+
+    fn drain<M: Message>(app: &mut App) -> Vec<M> {
+        app.world_mut()
+            .resource_mut::<Messages<M>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn loads_plugins_at_startup() {
+        let mut app = app();
+        app.update();
+
+        assert_eq!(
+            app.world().resource::<CybirdContext<TestContext>>().names(),
+            ["a", "b"]
+        );
+        let loaded: Vec<String> = drain::<PluginLoaded>(&mut app)
+            .into_iter()
+            .map(|loaded| loaded.id)
+            .collect();
+        assert_eq!(loaded, ["a", "b"]);
+        assert!(drain::<PluginFailed>(&mut app).is_empty());
+    }
+
+    #[test]
+    fn stops_updating_a_plugin_after_it_fails() {
+        let mut app = app();
+        app.update();
+        app.update();
+
+        let failed = drain::<PluginUpdateFailed>(&mut app);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, "b");
+        assert_eq!(failed[0].error, "out of order");
+    }
+
+    #[test]
+    fn unloads_plugins_on_request() {
+        let mut app = app();
+        app.update();
+
+        app.world_mut().write_message(UnloadPlugin("a".into()));
+        app.update();
+        let unloaded = drain::<PluginUnloaded>(&mut app);
+        assert_eq!(unloaded.len(), 1);
+        assert_eq!(unloaded[0].id, "a");
+        assert_eq!(
+            app.world().resource::<CybirdContext<TestContext>>().names(),
+            ["b"]
+        );
+
+        app.world_mut().write_message(UnloadPlugin("a".into()));
+        app.update();
+        let failed = drain::<PluginFailed>(&mut app);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].source, "a");
+    }
+}
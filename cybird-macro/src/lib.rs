@@ -1,26 +1,88 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{Data, ItemStruct, parse_macro_input};
-// Alternative: An attribute macro that adds the include automatically
+use syn::{Attribute, Data, DeriveInput, ItemStruct, parse_macro_input};
+
+/// Marks a plugin struct built for the context `context = MyContext`.
+///
+/// The plugin is submitted to cybird's static registry, so hosts that link it
+/// can find it through `cybird::static_plugins::<MyContext>()`, and to the
+/// plugin table a `cdylib` exports when cybird's `dynamic` feature is on. The
+/// struct must implement `Plugin<MyContext>`, `Send` and `Sync`.
+///
+/// The plugin is built with `Default` unless `create = path::to::function`
+/// names a `fn(Option<&str>) -> cybird::Result<Self>` that builds it from the
+/// host's configuration.
 #[proc_macro_attribute]
-pub fn plugin(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn plugin(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as ItemStruct);
+    let name = &input.ident;
+
+    let mut context: Option<syn::Type> = None;
+    let mut create: Option<syn::Path> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("context") {
+            context = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("create") {
+            create = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error(
+                "unsupported plugin attribute, expected `context = Type` or `create = function`",
+            ))
+        }
+    });
+    parse_macro_input!(attr with parser);
+
+    let Some(context) = context else {
+        return syn::Error::new_spanned(
+            &input.ident,
+            "missing `context = Type`, the context the plugin is built for",
+        )
+        .to_compile_error()
+        .into();
+    };
+
+    let constructor = match create {
+        Some(create) => quote! { #create(config) },
+        None => quote! {
+            let _ = config;
+            ::std::result::Result::Ok(<#name as ::std::default::Default>::default())
+        },
+    };
 
-    // Keep the original struct and add the include for generated exports
     let expanded = quote! {
         #input
 
-        // Include the build script generated C exports
-        include!(concat!(env!("OUT_DIR"), "/auto_exports.rs"));
+        impl ::cybird::Create for #name {
+            fn create(config: ::std::option::Option<&str>) -> ::cybird::Result<Self> {
+                #constructor
+            }
+        }
+
+        ::cybird::inventory::submit! {
+            ::cybird::registry::StaticPlugin {
+                context: ::std::any::TypeId::of::<#context>,
+                create: || {
+                    let plugin = <#name as ::cybird::Create>::create(None)?;
+                    let plugin: ::cybird::loader::BoxedPlugin<#context> =
+                        ::std::boxed::Box::new(plugin);
+                    ::std::result::Result::Ok(::std::boxed::Box::new(plugin))
+                },
+            }
+        }
+
+        // The entry also checks that the struct is a plugin for the context
+        ::cybird::inventory::submit! {
+            ::cybird::native::ExportedPlugin(
+                ::cybird::native::PluginEntry::new::<#context, #name>()
+            )
+        }
     };
 
     TokenStream::from(expanded)
 }
 
-use syn::{Attribute, DeriveInput};
-
-// ... existing plugin macro ...
-
 #[proc_macro_derive(Registrable, attributes(registrable))]
 pub fn derive_registrable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -94,6 +156,10 @@ fn extract_variant_info(attrs: &[Attribute]) -> Option<(proc_macro2::TokenStream
     None
 }
 
+/// Implements `Context` for a struct holding its registrables in a `Vec`.
+///
+/// A field of type `cybird::Owners` next to the `Vec` makes the context
+/// record which plugin registered each item.
 #[proc_macro_derive(Context, attributes(context))]
 pub fn derive_context(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -113,6 +179,27 @@ pub fn derive_context(input: TokenStream) -> TokenStream {
         }
     };
 
+    let owners = find_owners_field(&input.data);
+    let truncate_owners = owners.as_ref().map(|owners| {
+        quote! { self.#owners.truncate(len); }
+    });
+    let clear_owners = owners.as_ref().map(|owners| {
+        quote! { self.#owners.clear(); }
+    });
+    let owner_methods = owners.as_ref().map(|owners| {
+        quote! {
+            fn owner(&self, index: usize) -> Option<&str> {
+                self.#owners.get(index)
+            }
+
+            fn set_owner(&mut self, index: usize, owner: &str) {
+                if index < self.#storage_access.len() {
+                    self.#owners.set(index, owner);
+                }
+            }
+        }
+    });
+
     let expanded = quote! {
         impl #impl_generics Context for #name #ty_generics #where_clause {
             type Registrable = #registrable_type;
@@ -124,6 +211,24 @@ pub fn derive_context(input: TokenStream) -> TokenStream {
                 self.#storage_access.push(registrable.into());
             }
 
+            fn registrables(&self) -> &[Self::Registrable] {
+                &self.#storage_access
+            }
+
+            fn registrables_mut(&mut self) -> &mut [Self::Registrable] {
+                &mut self.#storage_access
+            }
+
+            fn truncate(&mut self, len: usize) {
+                self.#storage_access.truncate(len);
+                #truncate_owners
+            }
+
+            fn take_registrables(&mut self) -> Vec<Self::Registrable> {
+                #clear_owners
+                std::mem::take(&mut self.#storage_access)
+            }
+
             fn get_registrables<T>(&self) -> Vec<&T>
             where
                 T: FromRegistrable<Self::Registrable>,
@@ -143,6 +248,8 @@ pub fn derive_context(input: TokenStream) -> TokenStream {
                     .filter_map(|registrable| T::from_registrable_mut(registrable))
                     .collect()
             }
+
+            #owner_methods
         }
     };
 
@@ -155,10 +262,10 @@ fn extract_context_info(
 ) -> Option<(syn::Type, proc_macro2::TokenStream)> {
     // First, check for explicit attributes
     for attr in attrs {
-        if attr.path().is_ident("context") {
-            if let Ok(meta_list) = attr.meta.require_list() {
-                return parse_context_attribute(&meta_list.tokens);
-            }
+        if attr.path().is_ident("context")
+            && let Ok(meta_list) = attr.meta.require_list()
+        {
+            return parse_context_attribute(&meta_list.tokens);
         }
     }
 
@@ -170,21 +277,15 @@ fn extract_context_info(
                 .named
                 .iter()
                 .filter_map(|field| {
-                    if let syn::Type::Path(type_path) = &field.ty {
-                        if let Some(segment) = type_path.path.segments.last() {
-                            if segment.ident == "Vec" {
-                                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments
-                                {
-                                    if let Some(syn::GenericArgument::Type(inner_type)) =
-                                        args.args.first()
-                                    {
-                                        let field_name = field.ident.as_ref()?;
-                                        let access = quote! { #field_name };
-                                        return Some((inner_type.clone(), access));
-                                    }
-                                }
-                            }
-                        }
+                    if let syn::Type::Path(type_path) = &field.ty
+                        && let Some(segment) = type_path.path.segments.last()
+                        && segment.ident == "Vec"
+                        && let syn::PathArguments::AngleBracketed(args) = &segment.arguments
+                        && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+                    {
+                        let field_name = field.ident.as_ref()?;
+                        let access = quote! { #field_name };
+                        return Some((inner_type.clone(), access));
                     }
                     None
                 })
@@ -195,23 +296,24 @@ fn extract_context_info(
                 return Some((registrable_type.clone(), field_access.clone()));
             }
         } else if let syn::Fields::Unnamed(fields) = &data_struct.fields {
-            // Handle tuple struct with single Vec<T> field (like PluginContext(Vec<Registrable>))
-            if fields.unnamed.len() == 1 {
-                if let syn::Type::Path(type_path) = &fields.unnamed[0].ty {
-                    if let Some(segment) = type_path.path.segments.last() {
-                        if segment.ident == "Vec" {
-                            if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
-                                if let Some(syn::GenericArgument::Type(inner_type)) =
-                                    args.args.first()
-                                {
-                                    // For tuple structs, use numeric index
-                                    let field_access = quote! { 0 };
-                                    return Some((inner_type.clone(), field_access));
-                                }
-                            }
-                        }
-                    }
-                }
+            // Handle tuple struct with single Vec<T> field (like PluginContext(Vec<Registrable>)),
+            // optionally followed by its owners
+            let owners = fields
+                .unnamed
+                .iter()
+                .skip(1)
+                .all(|field| is_owners(&field.ty));
+            if owners
+                && fields.unnamed.len() <= 2
+                && let syn::Type::Path(type_path) = &fields.unnamed[0].ty
+                && let Some(segment) = type_path.path.segments.last()
+                && segment.ident == "Vec"
+                && let syn::PathArguments::AngleBracketed(args) = &segment.arguments
+                && let Some(syn::GenericArgument::Type(inner_type)) = args.args.first()
+            {
+                // For tuple structs, use numeric index
+                let field_access = quote! { 0 };
+                return Some((inner_type.clone(), field_access));
             }
         }
     }
@@ -219,6 +321,30 @@ fn extract_context_info(
     None
 }
 
+/// The field of type `Owners`, if the context records owners.
+fn find_owners_field(data: &Data) -> Option<proc_macro2::TokenStream> {
+    let Data::Struct(data_struct) = data else {
+        return None;
+    };
+    data_struct
+        .fields
+        .iter()
+        .enumerate()
+        .find(|(_, field)| is_owners(&field.ty))
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = syn::Index::from(index);
+                quote! { #index }
+            }
+        })
+}
+
+fn is_owners(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(type_path)
+        if type_path.path.segments.last().is_some_and(|segment| segment.ident == "Owners"))
+}
+
 fn parse_context_attribute(
     tokens: &proc_macro2::TokenStream,
 ) -> Option<(syn::Type, proc_macro2::TokenStream)> {
@@ -231,10 +357,10 @@ fn parse_context_attribute(
         let part = part.trim();
         if let Some(value) = part.strip_prefix("registrable =") {
             registrable_type = syn::parse_str::<syn::Type>(value.trim()).ok();
-        } else if let Some(value) = part.strip_prefix("field =") {
-            if let Ok(ident) = syn::parse_str::<syn::Ident>(value.trim()) {
-                field_name = Some(quote! { #ident });
-            }
+        } else if let Some(value) = part.strip_prefix("field =")
+            && let Ok(ident) = syn::parse_str::<syn::Ident>(value.trim())
+        {
+            field_name = Some(quote! { #ident });
         }
     }
 
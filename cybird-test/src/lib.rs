@@ -0,0 +1,243 @@
+//! Test helpers for plugin authors.
+//!
+//! A [`Harness`] loads plugins, compiled in or built as libraries, into a
+//! fresh context and offers assertions on what they registered:
+//!
+//! ```ignore
+//! let harness = cybird_test::load_plugin::<PluginContext, _>(CrazierCrab)?;
+//! harness.assert_count::<Upgrade>(2);
+//! assert_eq!(harness.assert_registered::<Upgrade>("Uber Oven").stage, 4);
+//! harness.assert_snapshot("crazier_crab");
+//! ```
+//!
+//! Items are looked up by the key their registrable type reports through
+//! [`Persist`].
+
+use cybird::loader::Loader;
+use cybird::native::NativePlugin;
+use cybird::snapshot::Persist;
+use cybird::{Context, FromRegistrable, Plugin, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Set to `1` to rewrite snapshots instead of comparing against them.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "CYBIRD_UPDATE_SNAPSHOTS";
+
+pub struct Harness<C: Context> {
+    context: C,
+    loader: Loader<C>,
+}
+
+impl<C: Context + Default + Clone> Default for Harness<C> {
+    fn default() -> Self {
+        Self {
+            context: C::default(),
+            loader: Loader::new(),
+        }
+    }
+}
+
+impl<C: Context + Default + Clone> Harness<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C: Context> Harness<C> {
+    /// Registers an item as the host would before any plugin loads.
+    pub fn with_host<T: Into<C::Registrable>>(mut self, registrable: T) -> Self {
+        self.context.register(registrable);
+        self
+    }
+
+    pub fn with_plugin<P>(mut self, plugin: P) -> Self
+    where
+        P: Plugin<C> + Send + Sync + 'static,
+    {
+        self.loader.add(plugin);
+        self
+    }
+
+    /// Adds every plugin for this context in the library at `path`, e.g.
+    /// from [`library_path`].
+    ///
+    /// # Safety
+    ///
+    /// See [`NativePlugin::open_all`].
+    pub unsafe fn with_library(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        for plugin in unsafe { NativePlugin::open_all(path)? } {
+            if plugin.is_for::<C>() {
+                self.loader.add(plugin);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Loads every added plugin.
+    pub fn load(mut self) -> Result<Self> {
+        self.loader.load(&mut self.context)?;
+        Ok(self)
+    }
+
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut C {
+        &mut self.context
+    }
+
+    pub fn loader(&self) -> &Loader<C> {
+        &self.loader
+    }
+
+    /// Every registered item of type `T`.
+    pub fn registered<T: FromRegistrable<C::Registrable>>(&self) -> Vec<&T> {
+        self.context.get_registrables()
+    }
+
+    /// Every item the plugin with the given id registered, for contexts that
+    /// record [`Context::owner`].
+    pub fn registered_by(&self, id: &str) -> Vec<&C::Registrable> {
+        self.context
+            .registrables()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.context.owner(*index) == Some(id))
+            .map(|(_, registrable)| registrable)
+            .collect()
+    }
+
+    /// Panics unless exactly `count` items of type `T` were registered.
+    #[track_caller]
+    pub fn assert_count<T: FromRegistrable<C::Registrable>>(&self, count: usize) {
+        let found = self.registered::<T>().len();
+        assert_eq!(
+            found,
+            count,
+            "expected {count} registered `{}`, found {found}",
+            std::any::type_name::<T>()
+        );
+    }
+}
+
+impl<C> Harness<C>
+where
+    C: Context,
+    C::Registrable: Persist,
+{
+    /// The item of type `T` registered under `key`.
+    pub fn get<T: FromRegistrable<C::Registrable>>(&self, key: &str) -> Option<&T> {
+        self.context
+            .registrables()
+            .iter()
+            .filter(|registrable| registrable.key() == Some(key))
+            .find_map(T::from_registrable)
+    }
+
+    /// Like [`Harness::get`], but panics with the registered keys if the
+    /// item is missing.
+    #[track_caller]
+    pub fn assert_registered<T: FromRegistrable<C::Registrable>>(&self, key: &str) -> &T {
+        match self.get(key) {
+            Some(item) => item,
+            None => panic!(
+                "no `{}` registered under `{key}`; registered keys: {:?}",
+                std::any::type_name::<T>(),
+                self.keys()
+            ),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        self.context
+            .registrables()
+            .iter()
+            .filter_map(Persist::key)
+            .collect()
+    }
+}
+
+impl<C> Harness<C>
+where
+    C: Context,
+    C::Registrable: Serialize,
+{
+    /// Compares every registration, as pretty JSON, with the snapshot
+    /// `tests/snapshots/<name>.json` of the crate under test.
+    ///
+    /// Snapshots are only written when [`UPDATE_SNAPSHOTS_VAR`] is set to
+    /// `1`, so a missing one fails the test instead of passing unchecked.
+    #[track_caller]
+    pub fn assert_snapshot(&self, name: &str) {
+        let actual = serde_json::to_string_pretty(self.context.registrables())
+            .expect("registrations serialize to JSON");
+        let path = snapshot_dir().join(format!("{name}.json"));
+
+        let update = std::env::var(UPDATE_SNAPSHOTS_VAR).is_ok_and(|value| value == "1");
+        if update {
+            std::fs::create_dir_all(snapshot_dir()).expect("create the snapshot directory");
+            std::fs::write(&path, actual + "\n").expect("write the snapshot");
+            return;
+        }
+
+        let expected = match std::fs::read_to_string(&path) {
+            Ok(expected) => expected,
+            Err(error) => panic!(
+                "cannot read snapshot {}: {error}; run with {UPDATE_SNAPSHOTS_VAR}=1 to write it",
+                path.display()
+            ),
+        };
+        assert!(
+            expected.trim_end() == actual,
+            "registrations differ from snapshot {}; rerun with {UPDATE_SNAPSHOTS_VAR}=1 to accept\n--- expected\n{expected}\n+++ actual\n{actual}",
+            path.display()
+        );
+    }
+}
+
+/// Loads a single plugin into a fresh context.
+pub fn load_plugin<C, P>(plugin: P) -> Result<Harness<C>>
+where
+    C: Context + Default + Clone,
+    P: Plugin<C> + Send + Sync + 'static,
+{
+    Harness::new().with_plugin(plugin).load()
+}
+
+/// Loads the plugins in the library at `path` into a fresh context.
+///
+/// # Safety
+///
+/// See [`NativePlugin::open_all`].
+pub unsafe fn load_library<C>(path: impl AsRef<Path>) -> Result<Harness<C>>
+where
+    C: Context + Default + Clone,
+{
+    unsafe { Harness::new().with_library(path)? }.load()
+}
+
+/// The path cargo builds the `cdylib` of the crate `name` to, for tests of
+/// the same workspace and profile, e.g. `target/debug/libcrazier_crab.so`.
+pub fn library_path(name: &str) -> PathBuf {
+    let test = std::env::current_exe().expect("locate the test executable");
+    // Test executables live in `target/<profile>/deps`
+    let profile_dir = test
+        .parent()
+        .and_then(Path::parent)
+        .expect("test executable is inside a target directory");
+
+    let file_name = format!(
+        "{}{}{}",
+        std::env::consts::DLL_PREFIX,
+        name.replace('-', "_"),
+        std::env::consts::DLL_SUFFIX
+    );
+    profile_dir.join(file_name)
+}
+
+fn snapshot_dir() -> PathBuf {
+    let manifest_dir =
+        std::env::var("CARGO_MANIFEST_DIR").expect("snapshots are compared under cargo test");
+    Path::new(&manifest_dir).join("tests").join("snapshots")
+}
@@ -0,0 +1,153 @@
+//! Icons, sounds, fonts and other files plugins ship with their content.
+//!
+//! Plugins name their assets in [`Plugin::assets`](crate::Plugin::assets),
+//! either as files relative to the directory they are installed in or as
+//! bytes compiled into the plugin:
+//!
+//! ```ignore
+//! fn assets(&self) -> Vec<(&str, Asset)> {
+//!     vec![
+//!         ("uber-oven", Asset::embedded(include_bytes!("../assets/uber-oven.png"))),
+//!         ("ding", Asset::file("sounds/ding.ogg")),
+//!     ]
+//! }
+//! ```
+//!
+//! Relative file paths are resolved when a plugin is opened, against the
+//! directory of a data plugin or the directory holding a library or script.
+//! Registrables refer to an asset by name, and the host looks it up under the
+//! plugin that registered them: after loading,
+//! [`Loader::load_assets`](crate::loader::Loader::load_assets) collects every
+//! plugin's assets into an [`AssetRegistry`], and
+//! [`Context::owner_of`](crate::Context::owner_of) finds the plugin behind
+//! an item.
+
+use crate::Result;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Asset {
+    File(PathBuf),
+    /// Bytes shared between every copy of the asset.
+    Embedded(Arc<[u8]>),
+}
+
+impl Asset {
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Asset::File(path.into())
+    }
+
+    pub fn embedded(bytes: &[u8]) -> Self {
+        Asset::Embedded(bytes.into())
+    }
+
+    /// The file's path, or `None` for embedded assets.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Asset::File(path) => Some(path),
+            Asset::Embedded(_) => None,
+        }
+    }
+
+    /// The asset's contents, read from disk for files.
+    pub fn read(&self) -> Result<Cow<'_, [u8]>> {
+        match self {
+            Asset::File(path) => std::fs::read(path)
+                .map(Cow::Owned)
+                .map_err(|err| format!("{}: {err}", path.display()).into()),
+            Asset::Embedded(bytes) => Ok(Cow::Borrowed(&bytes[..])),
+        }
+    }
+}
+
+/// Every loaded plugin's assets, by plugin id and asset name.
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    plugins: BTreeMap<String, BTreeMap<String, Asset>>,
+}
+
+impl AssetRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the asset `name` of plugin `id`.
+    pub fn insert(&mut self, id: impl Into<String>, name: impl Into<String>, asset: Asset) {
+        self.plugins
+            .entry(id.into())
+            .or_default()
+            .insert(name.into(), asset);
+    }
+
+    pub fn get(&self, id: &str, name: &str) -> Option<&Asset> {
+        self.plugins.get(id)?.get(name)
+    }
+
+    /// The assets of plugin `id`, sorted by name.
+    pub fn plugin_assets(&self, id: &str) -> impl Iterator<Item = (&str, &Asset)> {
+        self.plugins
+            .get(id)
+            .into_iter()
+            .flatten()
+            .map(|(name, asset)| (name.as_str(), asset))
+    }
+
+    /// Removes every asset of plugin `id`, for when it is unloaded.
+    pub fn remove_plugin(&mut self, id: &str) {
+        self.plugins.remove(id);
+    }
+
+    pub fn clear(&mut self) {
+        self.plugins.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_and_replaces_by_plugin_and_name() {
+        let mut assets = AssetRegistry::new();
+        assets.insert("a", "icon", Asset::file("old.png"));
+        assets.insert("a", "icon", Asset::file("new.png"));
+        assets.insert("b", "icon", Asset::embedded(b"png"));
+
+        assert_eq!(assets.get("a", "icon"), Some(&Asset::file("new.png")));
+        assert_eq!(assets.get("b", "icon"), Some(&Asset::embedded(b"png")));
+        assert_eq!(assets.get("a", "ding"), None);
+        assert_eq!(assets.get("c", "icon"), None);
+    }
+
+    #[test]
+    fn lists_and_removes_a_plugins_assets() {
+        let mut assets = AssetRegistry::new();
+        assets.insert("a", "zap", Asset::file("zap.ogg"));
+        assets.insert("a", "icon", Asset::file("icon.png"));
+        assets.insert("b", "icon", Asset::file("icon.png"));
+
+        let names: Vec<&str> = assets.plugin_assets("a").map(|(name, _)| name).collect();
+        assert_eq!(names, ["icon", "zap"]);
+
+        assets.remove_plugin("a");
+        assert_eq!(assets.plugin_assets("a").count(), 0);
+        assert!(assets.get("b", "icon").is_some());
+    }
+
+    #[test]
+    fn reads_embedded_and_file_assets() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("cybird-asset-{}", std::process::id()));
+        std::fs::write(&path, b"ding")?;
+        let read = Asset::file(&path).read().map(Cow::into_owned);
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(read?, b"ding");
+        assert_eq!(&*Asset::embedded(b"png").read()?, b"png");
+        assert!(Asset::file(&path).read().is_err());
+        assert_eq!(Asset::embedded(b"png").path(), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+//! Restricting what each plugin may do.
+//!
+//! Plugins declare the capabilities they use in the `capabilities` of
+//! [`Plugin::metadata`]: the [`Kind`] of every registrable they register,
+//! plus any host-defined services such as `"save-files"`. The host hands the
+//! loader a set of [`Grants`], and the loader then refuses plugins that ask
+//! for more than they were granted and rolls back registrations of kinds the
+//! plugin did not declare. Services are checked by the host itself through
+//! [`Loader::allows`](crate::loader::Loader::allows).
+//!
+//! Enforcement is advisory and not a security boundary. Only the items a
+//! plugin appends to the context are checked, so a plugin can still change
+//! or remove items it finds there, and native plugins run arbitrary code in
+//! the host process anyway. Grants keep well-behaved plugins honest; they do
+//! not contain hostile ones.
+
+use crate::{Context, Plugin, Result};
+use std::collections::{BTreeMap, HashMap};
+
+/// Names the kind of a registrable, e.g. `"upgrade"`, which is also the
+/// capability needed to register it.
+pub trait Kind {
+    /// Every kind a registrable can have. Declared capabilities that are not
+    /// listed are taken to be services, which
+    /// [`Loader::report`](crate::loader::Loader::report) does not expect the
+    /// plugin to register.
+    const KINDS: &'static [&'static str] = &[];
+
+    fn kind(&self) -> &str;
+}
+
+/// Capabilities granted to every plugin and to plugins by id.
+#[derive(Debug, Clone, Default)]
+pub struct Grants {
+    all: Vec<String>,
+    plugins: HashMap<String, Vec<String>>,
+}
+
+impl Grants {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn grant_all<I, S>(mut self, capabilities: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.all.extend(capabilities.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn grant<I, S>(mut self, plugin: &str, capabilities: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.plugins
+            .entry(plugin.to_string())
+            .or_default()
+            .extend(capabilities.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn is_granted(&self, plugin: &str, capability: &str) -> bool {
+        self.all.iter().any(|granted| granted == capability)
+            || self
+                .plugins
+                .get(plugin)
+                .is_some_and(|granted| granted.iter().any(|granted| granted == capability))
+    }
+}
+
+/// How to find the kind of a registrable, and which kinds there are.
+pub(crate) struct Kinds<R> {
+    pub kind: fn(&R) -> &str,
+    pub all: &'static [&'static str],
+}
+
+impl<R: Kind> Kinds<R> {
+    pub fn of() -> Self {
+        Self {
+            kind: R::kind,
+            all: R::KINDS,
+        }
+    }
+}
+
+impl<R> Kinds<R> {
+    /// How many of `registrables` there are of each kind.
+    pub fn count(&self, registrables: &[R]) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for registrable in registrables {
+            *counts
+                .entry((self.kind)(registrable).to_string())
+                .or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// Grants together with how to find the kind of a registrable.
+pub(crate) struct Policy<R> {
+    pub grants: Grants,
+    pub kind: fn(&R) -> &str,
+}
+
+impl<R> Policy<R> {
+    /// Fails if `plugin` declares a capability it was not granted.
+    pub fn check_declared<C>(&self, plugin: &dyn Plugin<C>) -> Result<()>
+    where
+        C: Context<Registrable = R>,
+    {
+        for capability in &plugin.metadata().capabilities {
+            if !self.grants.is_granted(plugin.id(), capability) {
+                return Err(format!(
+                    "plugin `{}` requires capability `{capability}`, which was not granted",
+                    plugin.id()
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if `plugin` registered an item whose kind it did not declare.
+    /// `registered` is what the plugin appended; changes it made to earlier
+    /// items are not seen.
+    pub fn check_registered<C>(&self, plugin: &dyn Plugin<C>, registered: &[R]) -> Result<()>
+    where
+        C: Context<Registrable = R>,
+    {
+        let metadata = plugin.metadata();
+        for registrable in registered {
+            let kind = (self.kind)(registrable);
+            if !metadata
+                .capabilities
+                .iter()
+                .any(|declared| declared == kind)
+            {
+                return Err(format!(
+                    "plugin `{}` registered an item of kind `{kind}` without the `{kind}` capability",
+                    plugin.id()
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::Loader;
+    use crate::testing::{TestContext, TestPlugin};
+
+    fn load(grants: Grants, plugin: TestPlugin) -> (Result<()>, TestContext) {
+        let mut ctx = TestContext::default();
+        let mut loader = Loader::new();
+        loader.set_grants(grants).add(plugin);
+        (loader.load(&mut ctx), ctx)
+    }
+
+    #[test]
+    fn grants_to_all_or_by_id() {
+        let grants = Grants::new()
+            .grant_all(["item"])
+            .grant("saver", ["save-files"]);
+        assert!(grants.is_granted("anyone", "item"));
+        assert!(grants.is_granted("saver", "save-files"));
+        assert!(!grants.is_granted("anyone", "save-files"));
+    }
+
+    #[test]
+    fn refuses_capabilities_that_were_not_granted() {
+        let plugin = TestPlugin::new("a").declares("item").registers("x");
+        let (result, ctx) = load(Grants::new(), plugin);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "plugin `a` requires capability `item`, which was not granted"
+        );
+        assert!(ctx.names().is_empty());
+    }
+
+    #[test]
+    fn rolls_back_undeclared_kinds() {
+        let plugin = TestPlugin::new("a").registers("x");
+        let (result, ctx) = load(Grants::new().grant_all(["item"]), plugin);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "plugin `a` registered an item of kind `item` without the `item` capability"
+        );
+        assert!(ctx.names().is_empty());
+    }
+
+    #[test]
+    fn allows_declared_and_granted_services() -> Result<()> {
+        let mut ctx = TestContext::default();
+        let mut loader = Loader::new();
+        loader
+            .set_grants(Grants::new().grant_all(["item"]).grant("a", ["save-files"]))
+            .add(TestPlugin::new("a").declares("save-files"))
+            .add(TestPlugin::new("b").declares("item").registers("x"));
+        loader.load(&mut ctx)?;
+
+        assert_eq!(ctx.names(), ["x"]);
+        assert!(loader.allows("a", "save-files"));
+        assert!(!loader.allows("b", "save-files"));
+        assert!(!loader.allows("a", "item"));
+        Ok(())
+    }
+}
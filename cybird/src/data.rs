@@ -0,0 +1,271 @@
+//! Plugins made only of data files.
+//!
+//! A data plugin is a directory with a `plugin.toml` manifest:
+//!
+//! ```toml
+//! id = "cookie-pack"
+//! author = "designer"
+//! version = "1.2.0"
+//! name = "Cookie Pack"
+//! description = "More ways to bake."
+//! tags = ["upgrades"]
+//! capabilities = ["upgrade"]
+//! icon = "icon.png"
+//! locales = "locales"
+//! dependencies = ["crazier-crab"]
+//! files = ["upgrades.ron"]
+//!
+//! [assets]
+//! cookie = "icons/cookie.png"
+//! ```
+//!
+//! Each listed file holds a list of the host's registrables and is
+//! deserialized according to its extension: `.ron` and `.json` files contain a
+//! top-level list, `.toml` files a `registrables` array. The optional
+//! [`Metadata`] fields may also be given, with `icon`, `locales` and the
+//! `assets` files relative to the plugin directory. A data plugin with only
+//! `locales` translates other plugins' content.
+
+use crate::asset::Asset;
+use crate::{Context, Metadata, Plugin, Result};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    pub author: String,
+    #[serde(default = "default_version")]
+    pub version: String,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+    #[serde(flatten)]
+    pub metadata: Metadata,
+}
+
+pub struct DataPlugin {
+    root: PathBuf,
+    manifest: Manifest,
+}
+
+#[derive(Deserialize)]
+struct TomlFile<R> {
+    registrables: Vec<R>,
+}
+
+impl DataPlugin {
+    /// Reads the manifest of the data plugin in `root`.
+    ///
+    /// Data files are only read when the plugin is loaded.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let manifest = std::fs::read_to_string(root.join(MANIFEST_FILE))?;
+        let mut manifest: Manifest = toml::from_str(&manifest)?;
+        if let Some(icon) = &mut manifest.metadata.icon {
+            *icon = root.join(&icon);
+        }
+        if let Some(locales) = &mut manifest.metadata.locales {
+            *locales = root.join(&locales);
+        }
+        for path in manifest.metadata.assets.values_mut() {
+            *path = root.join(&path);
+        }
+
+        Ok(Self { root, manifest })
+    }
+
+    /// Returns true if `dir` looks like a data plugin.
+    pub fn is_data_plugin(dir: impl AsRef<Path>) -> bool {
+        dir.as_ref().join(MANIFEST_FILE).is_file()
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    fn read_file<R: DeserializeOwned>(&self, file: &Path) -> Result<Vec<R>> {
+        let path = self.root.join(file);
+        let content =
+            std::fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+
+        let registrables: Result<Vec<R>> = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ron") => ron::from_str(&content).map_err(Into::into),
+            Some("json") => serde_json::from_str(&content).map_err(Into::into),
+            Some("toml") => toml::from_str::<TomlFile<R>>(&content)
+                .map(|file| file.registrables)
+                .map_err(Into::into),
+            _ => return Err(format!("unsupported data file `{}`", path.display()).into()),
+        };
+
+        registrables.map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+}
+
+impl<C> Plugin<C> for DataPlugin
+where
+    C: Context,
+    C::Registrable: DeserializeOwned,
+{
+    fn author(&self) -> &str {
+        &self.manifest.author
+    }
+
+    fn id(&self) -> &str {
+        &self.manifest.id
+    }
+
+    fn version(&self) -> &str {
+        &self.manifest.version
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Borrowed(&self.manifest.metadata)
+    }
+
+    fn assets(&self) -> Vec<(&str, Asset)> {
+        self.manifest.metadata.file_assets()
+    }
+
+    fn dependencies(&self) -> Vec<&str> {
+        self.manifest
+            .dependencies
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn load(&self, ctx: &mut C) -> Result<()> {
+        for file in &self.manifest.files {
+            for registrable in self.read_file::<C::Registrable>(file)? {
+                ctx.register(registrable);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_version() -> String {
+    "0.0.0".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Item, TestContext};
+
+    const MANIFEST: &str = r#"
+        id = "cookie-pack"
+        author = "designer"
+        name = "Cookie Pack"
+        tags = ["upgrades"]
+        icon = "icon.png"
+        locales = "locales"
+        files = ["one.ron", "two.json", "three.toml"]
+
+        [assets]
+        cookie = "icons/cookie.png"
+    "#;
+
+    /// A data plugin directory, deleted when dropped.
+    struct PluginDir(PathBuf);
+
+    impl PluginDir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Result<Self> {
+            let dir =
+                std::env::temp_dir().join(format!("cybird-data-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+            for (file, contents) in files {
+                std::fs::write(dir.join(file), contents)?;
+            }
+            Ok(Self(dir))
+        }
+    }
+
+    impl Drop for PluginDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn loads_every_format_in_order() -> Result<()> {
+        let dir = PluginDir::new(
+            "formats",
+            &[
+                (MANIFEST_FILE, MANIFEST),
+                ("one.ron", r#"[Item((name: "Ron", level: 1))]"#),
+                ("two.json", r#"[{"Item": {"name": "Json", "level": 2}}]"#),
+                (
+                    "three.toml",
+                    "[[registrables]]\nItem = { name = \"Toml\", level = 3 }\n",
+                ),
+            ],
+        )?;
+        let plugin = DataPlugin::open(&dir.0)?;
+        let mut ctx = TestContext::default();
+        Plugin::<TestContext>::load(&plugin, &mut ctx)?;
+
+        assert_eq!(ctx.names(), ["Ron", "Json", "Toml"]);
+        let levels: Vec<u32> = ctx
+            .get_registrables::<Item>()
+            .iter()
+            .map(|item| item.level)
+            .collect();
+        assert_eq!(levels, [1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_paths_against_the_plugin_directory() -> Result<()> {
+        let dir = PluginDir::new("paths", &[(MANIFEST_FILE, MANIFEST)])?;
+        let plugin = DataPlugin::open(&dir.0)?;
+        let plugin: &dyn Plugin<TestContext> = &plugin;
+
+        assert_eq!(plugin.id(), "cookie-pack");
+        assert_eq!(plugin.version(), "0.0.0");
+        let metadata = plugin.metadata();
+        assert_eq!(metadata.name.as_deref(), Some("Cookie Pack"));
+        assert_eq!(metadata.tags, ["upgrades"]);
+        assert_eq!(metadata.icon, Some(dir.0.join("icon.png")));
+        assert_eq!(metadata.locales, Some(dir.0.join("locales")));
+        let assets = plugin.assets();
+        assert_eq!(assets[0].0, "cookie");
+        assert_eq!(
+            assets[0].1.path(),
+            Some(dir.0.join("icons/cookie.png").as_path())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reports_the_file_that_fails() -> Result<()> {
+        let manifest = "id = \"broken\"\nauthor = \"designer\"\nfiles = [\"items.json\"]\n";
+        let dir = PluginDir::new(
+            "malformed",
+            &[(MANIFEST_FILE, manifest), ("items.json", "[{\"Item\": 1}]")],
+        )?;
+        let plugin = DataPlugin::open(&dir.0)?;
+        let mut ctx = TestContext::default();
+
+        let error = Plugin::<TestContext>::load(&plugin, &mut ctx).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "{}: invalid type: integer `1`, expected struct Item at line 1 column 11",
+                dir.0.join("items.json").display()
+            )
+        );
+        assert!(DataPlugin::open(dir.0.join("missing")).is_err());
+        Ok(())
+    }
+}
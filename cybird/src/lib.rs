@@ -1,13 +1,89 @@
 pub mod prelude {
     // Core types and traits
-    pub use crate::{Context, Plugin, Result};
+    pub use crate::query::Tagged;
+    pub use crate::{Context, Owners, Plugin, Result};
     pub use crate::{FromRegistrable, FromRegistrableMut};
 
     // Derive macros
     pub use cybird_macro::{Context, Registrable};
 }
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
 pub use cybird_macro::plugin;
+pub use registry::static_plugins;
+
+pub use tracing;
+
+#[doc(hidden)]
+pub use inventory;
+
+pub mod asset;
+pub mod capability;
+#[cfg(feature = "data")]
+pub mod data;
+pub mod loader;
+pub mod locale;
+pub mod log;
+pub mod native;
+#[cfg(feature = "process")]
+pub mod process;
+pub mod query;
+#[cfg(feature = "reflect")]
+pub mod reflect;
+pub mod registry;
+pub mod report;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod source;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "trust")]
+pub mod trust;
+
+/// Optional plugin metadata, returned by [`Plugin::metadata`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(
+    any(feature = "data", feature = "process"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Metadata {
+    /// A human-readable name for listings. Hosts show the id without one.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// A license expression such as `MIT OR Apache-2.0`.
+    pub license: Option<String>,
+    pub homepage: Option<String>,
+    /// Free-form keywords mod browsers can filter by.
+    #[cfg_attr(any(feature = "data", feature = "process"), serde(default))]
+    pub tags: Vec<String>,
+    pub icon: Option<PathBuf>,
+    /// The directory holding the plugin's translation files. See [`locale`].
+    pub locales: Option<PathBuf>,
+    /// File assets by name, for plugins that return them through
+    /// [`Metadata::file_assets`]. See [`asset`].
+    #[cfg_attr(any(feature = "data", feature = "process"), serde(default))]
+    pub assets: BTreeMap<String, PathBuf>,
+    /// Registrable kinds and host services the plugin uses. See
+    /// [`capability`].
+    #[cfg_attr(any(feature = "data", feature = "process"), serde(default))]
+    pub capabilities: Vec<String>,
+}
+
+impl Metadata {
+    /// [`Metadata::assets`] as [`Plugin::assets`] returns them.
+    pub fn file_assets(&self) -> Vec<(&str, asset::Asset)> {
+        self.assets
+            .iter()
+            .map(|(name, path)| (name.as_str(), asset::Asset::File(path.clone())))
+            .collect()
+    }
+}
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
@@ -15,7 +91,60 @@ pub trait Plugin<T: Context> {
     fn author(&self) -> &str;
     fn id(&self) -> &str;
 
+    /// The plugin's name, description, translations, capabilities and other
+    /// optional details. Plugins that read them from a file return them
+    /// borrowed.
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Owned(Metadata::default())
+    }
+
+    /// Files and embedded data the plugin's registrables refer to by name.
+    /// See [`asset`].
+    fn assets(&self) -> Vec<(&str, asset::Asset)> {
+        Vec::new()
+    }
+
+    /// Ids of the plugins that must be loaded before this one.
+    fn dependencies(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Recorded in snapshots so state saved by older versions can be migrated.
+    fn version(&self) -> &str {
+        "0.0.0"
+    }
+
+    /// Steps that bring state saved by older versions of this plugin up to
+    /// [`Plugin::version`].
+    #[cfg(feature = "snapshot")]
+    fn migrations(&self) -> Vec<snapshot::Migration> {
+        Vec::new()
+    }
+
+    /// Brings state saved by another version of this plugin up to date.
+    #[cfg(feature = "snapshot")]
+    fn migrate(&self, state: &mut snapshot::PluginState) -> Result<()> {
+        snapshot::migrate(state, self.version(), &self.migrations())
+    }
+
     fn load(&self, ctx: &mut T) -> Result<()>;
+
+    /// Called by the host on every tick once the plugin has loaded, with the
+    /// time since the previous tick. Plugins that only contribute data during
+    /// [`Plugin::load`] keep the default, which does nothing.
+    fn update(&mut self, ctx: &mut T, dt: Duration) -> Result<()> {
+        let _ = (ctx, dt);
+        Ok(())
+    }
+}
+
+/// Builds a plugin's instance, once per library load or registry lookup.
+///
+/// `#[cybird::plugin]` implements it with `Default`, or with the function
+/// named by `create = path`, which receives the host's configuration for the
+/// plugin if it has any.
+pub trait Create: Sized {
+    fn create(config: Option<&str>) -> Result<Self>;
 }
 
 pub trait Context {
@@ -25,6 +154,32 @@ pub trait Context {
     where
         T: Into<Self::Registrable>;
 
+    /// Every registered item, in registration order.
+    fn registrables(&self) -> &[Self::Registrable];
+
+    fn registrables_mut(&mut self) -> &mut [Self::Registrable];
+
+    /// Removes and returns every registered item.
+    fn take_registrables(&mut self) -> Vec<Self::Registrable>;
+
+    /// Removes every item registered after the first `len`.
+    ///
+    /// The default takes every item and registers the first `len` again,
+    /// keeping their owners. `#[derive(Context)]` truncates in place.
+    fn truncate(&mut self, len: usize) {
+        let kept = len.min(self.registrables().len());
+        let owners: Vec<Option<String>> = (0..kept)
+            .map(|index| self.owner(index).map(str::to_string))
+            .collect();
+        let registrables = self.take_registrables().into_iter().take(kept);
+        for (index, (registrable, owner)) in registrables.zip(owners).enumerate() {
+            self.register(registrable);
+            if let Some(owner) = owner {
+                self.set_owner(index, &owner);
+            }
+        }
+    }
+
     fn get_registrables<T>(&self) -> Vec<&T>
     where
         T: FromRegistrable<Self::Registrable>;
@@ -32,6 +187,71 @@ pub trait Context {
     fn get_registrables_mut<T>(&mut self) -> Vec<&mut T>
     where
         T: FromRegistrableMut<Self::Registrable>;
+
+    /// Registered items of type `T`, ready to be filtered by tag.
+    fn query<T>(&self) -> query::Query<'_, T>
+    where
+        T: FromRegistrable<Self::Registrable>,
+    {
+        query::Query::new(self.get_registrables())
+    }
+
+    /// The id of the plugin that registered the item at `index`, or `None`
+    /// for items the host registered and contexts that do not record owners.
+    fn owner(&self, index: usize) -> Option<&str> {
+        let _ = index;
+        None
+    }
+
+    /// Records that the plugin `owner` registered the item at `index`. The
+    /// loader calls this after every plugin; contexts that keep [`Owners`]
+    /// next to their items store it, and the default forgets it.
+    fn set_owner(&mut self, index: usize, owner: &str) {
+        let _ = (index, owner);
+    }
+
+    /// The id of the plugin that registered `item`, which must be borrowed
+    /// from this context, such as an item returned by
+    /// [`Context::get_registrables`].
+    fn owner_of<T>(&self, item: &T) -> Option<&str>
+    where
+        T: FromRegistrable<Self::Registrable>,
+    {
+        let index = self.registrables().iter().position(|registrable| {
+            T::from_registrable(registrable).is_some_and(|found| std::ptr::eq(found, item))
+        })?;
+        self.owner(index)
+    }
+}
+
+/// The plugin that registered each item of a context, by index.
+///
+/// `#[derive(Context)]` keeps a field of this type in step with the
+/// registrables, so ownership survives rollbacks, host registrations and
+/// removed plugins. Owners stay at their index, so an item moved within
+/// [`Context::registrables_mut`] loses its owner.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Owners(Vec<Option<String>>);
+
+impl Owners {
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.0.get(index)?.as_deref()
+    }
+
+    pub fn set(&mut self, index: usize, owner: &str) {
+        if self.0.len() <= index {
+            self.0.resize(index + 1, None);
+        }
+        self.0[index] = Some(owner.to_string());
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
 }
 
 pub trait FromRegistrable<R> {
@@ -0,0 +1,1095 @@
+//! Loading many plugins into one context in dependency order.
+
+use crate::asset::{Asset, AssetRegistry};
+use crate::capability::{Grants, Kind, Kinds, Policy};
+use crate::locale::Translations;
+use crate::report::{LoadReport, PluginReport};
+use crate::source::{Origin, PluginSource, SourceError};
+use crate::{Context, Plugin, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+pub type BoxedPlugin<C> = Box<dyn Plugin<C> + Send + Sync>;
+
+/// The outcome of one plugin in the most recent load, in load order.
+struct Loaded {
+    id: String,
+    /// How many items the plugin registered.
+    registered: usize,
+    /// How many items of each kind it registered, if kinds are counted.
+    registrations: BTreeMap<String, usize>,
+    duration: Duration,
+    error: Option<String>,
+    /// Why the plugin's last update failed, after which it is not updated
+    /// again.
+    update_error: Option<String>,
+    updates: UpdateTiming,
+}
+
+impl Loaded {
+    fn new<R>(
+        id: &str,
+        registered: &[R],
+        kinds: Option<&Kinds<R>>,
+        duration: Duration,
+        result: &Result<()>,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            registered: registered.len(),
+            registrations: kinds
+                .map(|kinds| kinds.count(registered))
+                .unwrap_or_default(),
+            duration,
+            error: result.as_ref().err().map(|error| error.to_string()),
+            update_error: None,
+            updates: UpdateTiming::default(),
+        }
+    }
+}
+
+/// How long a plugin's [`Plugin::update`] calls took since it was loaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UpdateTiming {
+    pub calls: u64,
+    pub last: Duration,
+    pub max: Duration,
+    pub total: Duration,
+}
+
+impl UpdateTiming {
+    pub fn mean(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            calls => self.total.div_f64(calls as f64),
+        }
+    }
+
+    fn record(&mut self, duration: Duration) {
+        self.calls += 1;
+        self.last = duration;
+        self.max = self.max.max(duration);
+        self.total += duration;
+    }
+}
+
+/// A plugin whose [`Plugin::update`] failed.
+#[derive(Debug)]
+pub struct UpdateError {
+    pub id: String,
+    pub error: Box<dyn std::error::Error>,
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.error)
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+/// Collects plugins of any kind and loads them so that every plugin is loaded
+/// after the plugins it depends on.
+///
+/// Each plugin loads and updates as a transaction: if it fails or panics,
+/// the whole context is restored from a clone taken before the call.
+/// Contexts that are not `Clone` use [`Loader::append_only`], which only
+/// removes what the plugin registered.
+///
+/// The loader owns its plugins after loading, which keeps native libraries
+/// alive for as long as the loader is.
+pub struct Loader<C: Context> {
+    plugins: Vec<BoxedPlugin<C>>,
+    origins: Vec<Origin>,
+    loaded: Vec<Loaded>,
+    open_errors: Vec<String>,
+    order_error: Option<String>,
+    policy: Option<Policy<C::Registrable>>,
+    kinds: Option<Kinds<C::Registrable>>,
+    checkpoint: Option<fn(&C) -> C>,
+}
+
+impl<C: Context + Clone> Default for Loader<C> {
+    fn default() -> Self {
+        Self::with_checkpoint(Some(C::clone))
+    }
+}
+
+impl<C: Context + Clone> Loader<C> {
+    /// A loader that clones the context before each plugin loads or updates
+    /// and puts the clone back if the plugin fails, so changes it made to
+    /// items registered by the host or earlier plugins are undone too.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<C: Context> Loader<C> {
+    /// A loader for contexts that cannot be cloned. A failed plugin's
+    /// registrations are still removed, but changes it made to items
+    /// registered before it stay.
+    pub fn append_only() -> Self {
+        Self::with_checkpoint(None)
+    }
+
+    fn with_checkpoint(checkpoint: Option<fn(&C) -> C>) -> Self {
+        Self {
+            plugins: Vec::new(),
+            origins: Vec::new(),
+            loaded: Vec::new(),
+            open_errors: Vec::new(),
+            order_error: None,
+            policy: None,
+            kinds: None,
+            checkpoint,
+        }
+    }
+
+    pub fn add<P>(&mut self, plugin: P) -> &mut Self
+    where
+        P: Plugin<C> + Send + Sync + 'static,
+    {
+        self.add_boxed(Box::new(plugin))
+    }
+
+    pub fn add_boxed(&mut self, plugin: BoxedPlugin<C>) -> &mut Self {
+        self.add_from(Origin::Static, plugin)
+    }
+
+    pub fn add_from(&mut self, origin: Origin, plugin: BoxedPlugin<C>) -> &mut Self {
+        self.plugins.push(plugin);
+        self.origins.push(origin);
+        self
+    }
+
+    /// Adds every plugin `source` opens, returning the ones that could not be
+    /// opened.
+    pub fn add_source(&mut self, source: &dyn PluginSource<C>) -> Vec<SourceError> {
+        let _span = tracing::info_span!("discover").entered();
+        let mut errors = Vec::new();
+        for (origin, plugin) in source.open() {
+            match plugin {
+                Ok(plugin) => {
+                    tracing::debug!(id = plugin.id(), %origin, "opened plugin");
+                    self.add_from(origin, plugin);
+                }
+                Err(error) => {
+                    let error = SourceError { origin, error };
+                    tracing::warn!(%error, "could not open plugin");
+                    self.open_errors.push(error.to_string());
+                    errors.push(error);
+                }
+            }
+        }
+        errors
+    }
+
+    pub fn plugins(&self) -> impl Iterator<Item = &BoxedPlugin<C>> {
+        self.plugins.iter()
+    }
+
+    pub fn plugin(&self, id: &str) -> Option<&BoxedPlugin<C>> {
+        self.plugins.iter().find(|plugin| plugin.id() == id)
+    }
+
+    pub fn origin(&self, id: &str) -> Option<&Origin> {
+        Some(&self.origins[self.index(id)?])
+    }
+
+    /// Loads every plugin into `ctx`, dependencies first.
+    ///
+    /// Plugins without an ordering constraint between them load in the order
+    /// they were added.
+    pub fn load(&mut self, ctx: &mut C) -> Result<()> {
+        let _span = tracing::info_span!("load", plugins = self.plugins.len()).entered();
+        for index in self.begin()? {
+            let plugin = self.plugins[index].as_ref();
+            let start = ctx.registrables().len();
+            let started = Instant::now();
+            let result = self.load_plugin(plugin, ctx);
+            self.loaded.push(Loaded::new(
+                plugin.id(),
+                appended(ctx, start),
+                self.kinds.as_ref(),
+                started.elapsed(),
+                &result,
+            ));
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Loads every plugin like [`Loader::load`], loading plugins without
+    /// dependencies in parallel.
+    ///
+    /// Each such plugin loads into its own empty `C::default()` context on the
+    /// rayon thread pool, and the results are merged into `ctx` in the same
+    /// order [`Loader::load`] would register them. Plugins with dependencies
+    /// load directly into `ctx` once everything before them is merged, so
+    /// they see what they depend on. Plugins that inspect the context should
+    /// therefore declare dependencies on the plugins they look at.
+    ///
+    /// Only the items returned by [`Context::take_registrables`] are merged
+    /// back from a scratch context. Anything else a plugin stores in it, such
+    /// as other fields of `C`, is dropped with the scratch context.
+    #[cfg(feature = "parallel")]
+    pub fn load_parallel(&mut self, ctx: &mut C) -> Result<()>
+    where
+        C: Default + Send,
+        C::Registrable: Send,
+    {
+        use rayon::prelude::*;
+
+        let span = tracing::info_span!("load", plugins = self.plugins.len(), parallel = true);
+        let _entered = span.enter();
+        let order = self.begin()?;
+        let mut position = 0;
+        while position < order.len() {
+            let independent = order[position..]
+                .iter()
+                .take_while(|&&index| self.plugins[index].dependencies().is_empty())
+                .count();
+
+            if independent == 0 {
+                let plugin = self.plugins[order[position]].as_ref();
+                let start = ctx.registrables().len();
+                let started = Instant::now();
+                let result = self.load_plugin(plugin, ctx);
+                self.loaded.push(Loaded::new(
+                    plugin.id(),
+                    appended(ctx, start),
+                    self.kinds.as_ref(),
+                    started.elapsed(),
+                    &result,
+                ));
+                result?;
+                position += 1;
+                continue;
+            }
+
+            let batch = &order[position..position + independent];
+            let loader = &*self;
+            let scratch: Vec<(C, Duration, std::result::Result<(), String>)> = batch
+                .par_iter()
+                .map(|&index| {
+                    let mut scratch = C::default();
+                    let started = Instant::now();
+                    let result = span.in_scope(|| {
+                        loader.load_plugin(loader.plugins[index].as_ref(), &mut scratch)
+                    });
+                    let duration = started.elapsed();
+                    (scratch, duration, result.map_err(|error| error.to_string()))
+                })
+                .collect();
+
+            for (&index, (mut scratch, duration, result)) in batch.iter().zip(scratch) {
+                let start = ctx.registrables().len();
+                for registrable in scratch.take_registrables() {
+                    ctx.register(registrable);
+                }
+                set_owners(ctx, start, self.plugins[index].id());
+                let result = result.map_err(Into::into);
+                self.loaded.push(Loaded::new(
+                    self.plugins[index].id(),
+                    appended(ctx, start),
+                    self.kinds.as_ref(),
+                    duration,
+                    &result,
+                ));
+                result?;
+            }
+            position += independent;
+        }
+        Ok(())
+    }
+
+    /// Loads every plugin like [`Loader::load`], but keeps going when a plugin
+    /// fails and reports each outcome to `on_result`.
+    ///
+    /// Plugins whose dependencies failed are reported as failed without being
+    /// loaded. Only an unresolvable ordering is returned as an error.
+    pub fn load_each<F>(&mut self, ctx: &mut C, mut on_result: F) -> Result<()>
+    where
+        F: FnMut(&dyn Plugin<C>, Result<()>),
+    {
+        let _span = tracing::info_span!("load", plugins = self.plugins.len()).entered();
+        let order = self.begin()?;
+        let mut failed: Vec<&str> = Vec::new();
+        for index in order {
+            let plugin = self.plugins[index].as_ref();
+            let start = ctx.registrables().len();
+            let started = Instant::now();
+            let result = match plugin
+                .dependencies()
+                .into_iter()
+                .find(|dependency| failed.contains(dependency))
+            {
+                Some(dependency) => {
+                    tracing::warn!(
+                        id = plugin.id(),
+                        dependency,
+                        "skipped after dependency failed"
+                    );
+                    Err(format!("dependency `{dependency}` failed to load").into())
+                }
+                None => self.load_plugin(plugin, ctx),
+            };
+            self.loaded.push(Loaded::new(
+                plugin.id(),
+                appended(ctx, start),
+                self.kinds.as_ref(),
+                started.elapsed(),
+                &result,
+            ));
+
+            if result.is_err() {
+                failed.push(plugin.id());
+            }
+            on_result(plugin, result);
+        }
+        Ok(())
+    }
+
+    /// Calls [`Plugin::update`] on every plugin that loaded in the most recent
+    /// load, in load order, and times each call.
+    ///
+    /// A plugin that fails or panics does not stop the others. The context is
+    /// rolled back like after a failed load, its error is returned, and it is
+    /// not updated again until the next load.
+    pub fn update(&mut self, ctx: &mut C, dt: Duration) -> Vec<UpdateError> {
+        let mut errors = Vec::new();
+        for loaded in self
+            .loaded
+            .iter_mut()
+            .filter(|loaded| loaded.error.is_none() && loaded.update_error.is_none())
+        {
+            let Some(plugin) = self
+                .plugins
+                .iter_mut()
+                .find(|plugin| plugin.id() == loaded.id)
+            else {
+                continue;
+            };
+            let _span = tracing::trace_span!("update", id = loaded.id).entered();
+
+            let start = ctx.registrables().len();
+            let checkpoint = self.checkpoint.map(|clone| clone(ctx));
+            let started = Instant::now();
+            let mut result = match panic::catch_unwind(AssertUnwindSafe(|| plugin.update(ctx, dt)))
+            {
+                Ok(result) => result,
+                Err(payload) => Err(format!(
+                    "plugin `{}` panicked: {}",
+                    loaded.id,
+                    panic_message(payload.as_ref())
+                )
+                .into()),
+            };
+            loaded.updates.record(started.elapsed());
+
+            if let (Ok(()), Some(policy)) = (&result, &self.policy) {
+                result = policy.check_registered(plugin.as_ref(), appended(ctx, start));
+            }
+            match result {
+                Ok(()) => {
+                    let registered = appended(ctx, start);
+                    loaded.registered += registered.len();
+                    if let Some(kinds) = &self.kinds {
+                        for (kind, count) in kinds.count(registered) {
+                            *loaded.registrations.entry(kind).or_default() += count;
+                        }
+                    }
+                    set_owners(ctx, start, &loaded.id);
+                }
+                Err(error) => {
+                    match checkpoint {
+                        Some(checkpoint) => *ctx = checkpoint,
+                        None => ctx.truncate(start),
+                    }
+                    tracing::warn!(%error, id = loaded.id, "plugin failed to update");
+                    loaded.update_error = Some(error.to_string());
+                    errors.push(UpdateError {
+                        id: loaded.id.clone(),
+                        error,
+                    });
+                }
+            }
+        }
+        errors
+    }
+
+    /// How long the updates of the plugin with the given id took, if it
+    /// loaded.
+    pub fn update_timing(&self, id: &str) -> Option<&UpdateTiming> {
+        self.loaded
+            .iter()
+            .find(|loaded| loaded.id == id && loaded.error.is_none())
+            .map(|loaded| &loaded.updates)
+    }
+
+    /// The update timings of every loaded plugin, slowest on average first.
+    pub fn update_timings(&self) -> Vec<(&str, &UpdateTiming)> {
+        let mut timings: Vec<(&str, &UpdateTiming)> = self
+            .loaded
+            .iter()
+            .filter(|loaded| loaded.error.is_none())
+            .map(|loaded| (loaded.id.as_str(), &loaded.updates))
+            .collect();
+        timings.sort_by_key(|(_, timing)| std::cmp::Reverse(timing.mean()));
+        timings
+    }
+
+    /// Reads the translation files of every plugin that loaded, in load
+    /// order, so a plugin can replace the texts of the plugins it depends
+    /// on. Returns the plugins whose files could not be read; their other
+    /// files may still have been added.
+    pub fn load_translations(&self, translations: &mut Translations) -> Vec<String> {
+        let mut errors = Vec::new();
+        for loaded in self.loaded.iter().filter(|loaded| loaded.error.is_none()) {
+            let Some(plugin) = self.plugin(&loaded.id) else {
+                continue;
+            };
+            let Some(dir) = plugin.metadata().locales.clone() else {
+                continue;
+            };
+            if let Err(error) = translations.load_dir(&dir) {
+                tracing::warn!(%error, id = loaded.id, "could not read translations");
+                errors.push(format!("{}: {error}", loaded.id));
+            }
+        }
+        errors
+    }
+
+    /// Adds the assets of every plugin that loaded to `assets`. Returns the
+    /// file assets that do not exist, which are left out.
+    pub fn load_assets(&self, assets: &mut AssetRegistry) -> Vec<String> {
+        let mut errors = Vec::new();
+        for loaded in self.loaded.iter().filter(|loaded| loaded.error.is_none()) {
+            let Some(plugin) = self.plugin(&loaded.id) else {
+                continue;
+            };
+            for (name, asset) in plugin.assets() {
+                if let Asset::File(path) = &asset
+                    && !path.is_file()
+                {
+                    let error = format!("asset `{name}` not found at {}", path.display());
+                    tracing::warn!(%error, id = loaded.id, "missing asset");
+                    errors.push(format!("{}: {error}", loaded.id));
+                    continue;
+                }
+                assets.insert(&loaded.id, name, asset);
+            }
+        }
+        errors
+    }
+
+    /// Whether the plugin with the given id may use `capability`: it must
+    /// have declared it and, if grants are set, been granted it.
+    pub fn allows(&self, id: &str, capability: &str) -> bool {
+        let Some(plugin) = self.plugin(id) else {
+            return false;
+        };
+        plugin
+            .metadata()
+            .capabilities
+            .iter()
+            .any(|c| c == capability)
+            && self
+                .policy
+                .as_ref()
+                .is_none_or(|policy| policy.grants.is_granted(id, capability))
+    }
+
+    /// Removes the plugin with the given id and hands it back.
+    ///
+    /// Dropping a native plugin unloads its library. Anything the plugin
+    /// registered stays in the context, so remove it first if it refers to
+    /// code in the library.
+    pub fn remove(&mut self, id: &str) -> Option<BoxedPlugin<C>> {
+        let index = self.index(id)?;
+        self.origins.remove(index);
+        Some(self.plugins.remove(index))
+    }
+
+    /// Removes the plugin with the given id together with every item it owns
+    /// in `ctx`, and hands the plugin back.
+    ///
+    /// Fails without changing anything if the plugin registered items but
+    /// `ctx` does not record their [`Context::owner`], as they could not be
+    /// told apart from the rest.
+    pub fn unload(&mut self, ctx: &mut C, id: &str) -> Result<BoxedPlugin<C>> {
+        if self.index(id).is_none() {
+            return Err(format!("plugin `{id}` is not loaded").into());
+        }
+
+        let owners: Vec<Option<String>> = (0..ctx.registrables().len())
+            .map(|index| ctx.owner(index).map(str::to_string))
+            .collect();
+        let registered = self
+            .loaded
+            .iter()
+            .any(|loaded| loaded.id == id && loaded.registered > 0);
+        if registered && !owners.iter().any(|owner| owner.as_deref() == Some(id)) {
+            return Err(format!(
+                "plugin `{id}` registered items the context has no owner for, so they cannot be removed"
+            )
+            .into());
+        }
+
+        for (registrable, owner) in ctx.take_registrables().into_iter().zip(owners) {
+            if owner.as_deref() == Some(id) {
+                continue;
+            }
+            let index = ctx.registrables().len();
+            ctx.register(registrable);
+            if let Some(owner) = owner {
+                ctx.set_owner(index, &owner);
+            }
+        }
+        self.loaded.retain(|loaded| loaded.id != id);
+        Ok(self.remove(id).expect("the plugin is loaded"))
+    }
+
+    /// Loads one plugin in its own span, enforcing the grants if there are
+    /// any, and rolls `ctx` back if the plugin fails.
+    fn load_plugin(&self, plugin: &dyn Plugin<C>, ctx: &mut C) -> Result<()> {
+        let _span =
+            tracing::info_span!("plugin", id = plugin.id(), version = plugin.version()).entered();
+        let result = self.try_load_plugin(plugin, ctx);
+        if let Err(error) = &result {
+            tracing::warn!(%error, "plugin failed to load");
+        }
+        result
+    }
+
+    fn try_load_plugin(&self, plugin: &dyn Plugin<C>, ctx: &mut C) -> Result<()> {
+        if let Some(policy) = &self.policy {
+            policy.check_declared(plugin)?;
+        }
+
+        let start = ctx.registrables().len();
+        let checkpoint = self.checkpoint.map(|clone| clone(ctx));
+        let result = self.run_plugin(plugin, ctx, start);
+        match result {
+            Ok(()) => set_owners(ctx, start, plugin.id()),
+            Err(_) => match checkpoint {
+                Some(checkpoint) => *ctx = checkpoint,
+                None => ctx.truncate(start),
+            },
+        }
+        result
+    }
+
+    fn run_plugin(&self, plugin: &dyn Plugin<C>, ctx: &mut C, start: usize) -> Result<()> {
+        match panic::catch_unwind(AssertUnwindSafe(|| plugin.load(ctx))) {
+            Ok(result) => result?,
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                return Err(format!("plugin `{}` panicked: {message}", plugin.id()).into());
+            }
+        }
+
+        if let Some(policy) = &self.policy {
+            policy.check_registered(plugin, appended(ctx, start))?;
+        }
+        tracing::debug!(
+            registered = ctx.registrables().len().saturating_sub(start),
+            "plugin loaded"
+        );
+        Ok(())
+    }
+
+    /// Describes the most recent load: for every plugin its origin, version,
+    /// position in the load order, load time, warnings and error, and its
+    /// registrations by kind if kinds are counted, see
+    /// [`Loader::count_kinds`].
+    pub fn report(&self) -> LoadReport {
+        let mut plugins: Vec<PluginReport> = Vec::new();
+        for (position, loaded) in self.loaded.iter().enumerate() {
+            let Some(index) = self.index(&loaded.id) else {
+                continue;
+            };
+            let plugin = &self.plugins[index];
+            let mut report = self.plugin_report(index, Some(position));
+            report.duration = loaded.duration;
+            report.error = loaded.error.clone();
+            report.registrations = loaded.registrations.clone();
+            report.registered = loaded.registered;
+
+            if report.error.is_none() {
+                if loaded.registered == 0 {
+                    report.warnings.push("registered nothing".to_string());
+                } else {
+                    let kinds = self.kinds.as_ref().map_or(&[][..], |kinds| kinds.all);
+                    for capability in &plugin.metadata().capabilities {
+                        if kinds.contains(&capability.as_str())
+                            && !report.registrations.contains_key(capability)
+                        {
+                            report.warnings.push(format!(
+                                "declared the `{capability}` capability but registered nothing of that kind"
+                            ));
+                        }
+                    }
+                }
+            }
+            if let Some(error) = &loaded.update_error {
+                report
+                    .warnings
+                    .push(format!("stopped updating after an error: {error}"));
+            }
+            plugins.push(report);
+        }
+
+        for index in 0..self.plugins.len() {
+            let id = self.plugins[index].id();
+            if !self.loaded.iter().any(|loaded| loaded.id == id) {
+                plugins.push(self.plugin_report(index, None));
+            }
+        }
+
+        LoadReport {
+            plugins,
+            errors: self
+                .open_errors
+                .iter()
+                .chain(&self.order_error)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn plugin_report(&self, index: usize, position: Option<usize>) -> PluginReport {
+        let plugin = &self.plugins[index];
+        let origin = &self.origins[index];
+        PluginReport {
+            id: plugin.id().to_string(),
+            version: plugin.version().to_string(),
+            origin: origin.to_string(),
+            path: origin.path().map(Into::into),
+            position,
+            duration: Duration::ZERO,
+            registrations: Default::default(),
+            registered: 0,
+            warnings: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn index(&self, id: &str) -> Option<usize> {
+        self.plugins.iter().position(|plugin| plugin.id() == id)
+    }
+
+    /// Forgets the previous load and returns the order to load plugins in.
+    fn begin(&mut self) -> Result<Vec<usize>> {
+        self.loaded.clear();
+        let order = self.order();
+        self.order_error = order.as_ref().err().map(|error| error.to_string());
+        order
+    }
+
+    /// Returns plugin indices sorted so that dependencies come first.
+    fn order(&self) -> Result<Vec<usize>> {
+        let ids: Vec<&str> = self.plugins.iter().map(|plugin| plugin.id()).collect();
+        for (index, id) in ids.iter().enumerate() {
+            if ids[..index].contains(id) {
+                return Err(format!("duplicate plugin id `{id}`").into());
+            }
+        }
+
+        let mut dependencies = Vec::with_capacity(self.plugins.len());
+        for plugin in &self.plugins {
+            let mut indices = Vec::new();
+            for dependency in plugin.dependencies() {
+                let Some(index) = ids.iter().position(|id| *id == dependency) else {
+                    return Err(format!(
+                        "plugin `{}` depends on `{dependency}`, which is not available",
+                        plugin.id()
+                    )
+                    .into());
+                };
+                indices.push(index);
+            }
+            dependencies.push(indices);
+        }
+
+        let mut order = Vec::with_capacity(self.plugins.len());
+        let mut placed = vec![false; self.plugins.len()];
+        while order.len() < self.plugins.len() {
+            let next = (0..self.plugins.len()).find(|&index| {
+                !placed[index] && dependencies[index].iter().all(|&dep| placed[dep])
+            });
+
+            let Some(index) = next else {
+                let cycle: Vec<&str> = (0..ids.len())
+                    .filter(|&index| !placed[index])
+                    .map(|index| ids[index])
+                    .collect();
+                return Err(format!("dependency cycle between {}", cycle.join(", ")).into());
+            };
+
+            placed[index] = true;
+            order.push(index);
+        }
+
+        Ok(order)
+    }
+}
+
+impl<C> Loader<C>
+where
+    C: Context,
+    C::Registrable: Kind,
+{
+    /// Enforces `grants` on every plugin loaded from now on, and counts
+    /// registrations by kind like [`Loader::count_kinds`]. See
+    /// [`capability`](crate::capability).
+    pub fn set_grants(&mut self, grants: Grants) -> &mut Self {
+        self.policy = Some(Policy {
+            grants,
+            kind: <C::Registrable as Kind>::kind,
+        });
+        self.count_kinds()
+    }
+
+    /// Counts what every plugin loaded from now on registers by [`Kind`],
+    /// for [`Loader::report`].
+    pub fn count_kinds(&mut self) -> &mut Self {
+        self.kinds = Some(Kinds::of());
+        self
+    }
+}
+
+/// The items appended to `ctx` since it held `start` of them.
+fn appended<C: Context>(ctx: &C, start: usize) -> &[C::Registrable] {
+    ctx.registrables().get(start..).unwrap_or_default()
+}
+
+/// Records `id` as the owner of every item from `start` on.
+fn set_owners<C: Context>(ctx: &mut C, start: usize, id: &str) {
+    for index in start..ctx.registrables().len() {
+        ctx.set_owner(index, id);
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Item, TestContext, TestPlugin};
+
+    fn loader(plugins: Vec<TestPlugin>) -> Loader<TestContext> {
+        let mut loader = Loader::new();
+        for plugin in plugins {
+            loader.add(plugin);
+        }
+        loader
+    }
+
+    fn load_order(plugins: Vec<TestPlugin>) -> Result<Vec<String>> {
+        let mut ctx = TestContext::default();
+        loader(plugins).load(&mut ctx)?;
+        Ok(ctx.names().into_iter().map(str::to_string).collect())
+    }
+
+    #[test]
+    fn loads_dependencies_first() -> Result<()> {
+        let order = load_order(vec![
+            TestPlugin::new("c").registers("c").depends_on("b"),
+            TestPlugin::new("b").registers("b").depends_on("a"),
+            TestPlugin::new("a").registers("a"),
+        ])?;
+        assert_eq!(order, ["a", "b", "c"]);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_the_order_plugins_were_added_in() -> Result<()> {
+        let order = load_order(vec![
+            TestPlugin::new("z").registers("z"),
+            TestPlugin::new("late").registers("late").depends_on("m"),
+            TestPlugin::new("m").registers("m"),
+            TestPlugin::new("a").registers("a"),
+        ])?;
+        assert_eq!(order, ["z", "m", "late", "a"]);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let error = load_order(vec![
+            TestPlugin::new("a"),
+            TestPlugin::new("b"),
+            TestPlugin::new("a"),
+        ])
+        .unwrap_err();
+        assert_eq!(error.to_string(), "duplicate plugin id `a`");
+    }
+
+    #[test]
+    fn rejects_missing_dependencies() {
+        let error = load_order(vec![TestPlugin::new("a").depends_on("gone")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "plugin `a` depends on `gone`, which is not available"
+        );
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let error = load_order(vec![
+            TestPlugin::new("free"),
+            TestPlugin::new("a").depends_on("b"),
+            TestPlugin::new("b").depends_on("c"),
+            TestPlugin::new("c").depends_on("a"),
+        ])
+        .unwrap_err();
+        assert_eq!(error.to_string(), "dependency cycle between a, b, c");
+
+        let error = load_order(vec![TestPlugin::new("self").depends_on("self")]).unwrap_err();
+        assert_eq!(error.to_string(), "dependency cycle between self");
+    }
+
+    #[test]
+    fn nothing_loads_when_the_order_fails() {
+        let mut ctx = TestContext::default();
+        let mut loader = loader(vec![
+            TestPlugin::new("a").registers("a"),
+            TestPlugin::new("a").registers("again"),
+        ]);
+        assert!(loader.load(&mut ctx).is_err());
+        assert!(ctx.names().is_empty());
+    }
+
+    /// Raises the level of every item already registered, then fails.
+    struct Meddler;
+
+    impl Plugin<TestContext> for Meddler {
+        fn author(&self) -> &str {
+            "tests"
+        }
+
+        fn id(&self) -> &str {
+            "meddler"
+        }
+
+        fn load(&self, ctx: &mut TestContext) -> Result<()> {
+            for item in ctx.get_registrables_mut::<Item>() {
+                item.level += 1;
+            }
+            ctx.register(Item::new("meddled"));
+            Err("meddled".into())
+        }
+    }
+
+    #[test]
+    fn rolls_back_the_whole_context() {
+        let mut ctx = TestContext::default();
+        ctx.register(Item::new("host"));
+        let mut loader = Loader::new();
+        loader.add(Meddler);
+        assert!(loader.load(&mut ctx).is_err());
+        assert_eq!(ctx.names(), ["host"]);
+        assert_eq!(ctx.get_registrables::<Item>()[0].level, 0);
+
+        let mut loader = Loader::append_only();
+        loader.add(Meddler);
+        assert!(loader.load(&mut ctx).is_err());
+        assert_eq!(ctx.names(), ["host"]);
+        assert_eq!(ctx.get_registrables::<Item>()[0].level, 1);
+    }
+
+    /// Takes every item out of the context.
+    struct Thief;
+
+    impl Plugin<TestContext> for Thief {
+        fn author(&self) -> &str {
+            "tests"
+        }
+
+        fn id(&self) -> &str {
+            "thief"
+        }
+
+        fn load(&self, ctx: &mut TestContext) -> Result<()> {
+            ctx.take_registrables();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn survives_plugins_that_remove_items() -> Result<()> {
+        let mut ctx = TestContext::default();
+        ctx.register(Item::new("host"));
+        let mut loader = Loader::new();
+        loader
+            .set_grants(Grants::new().grant_all(["item"]))
+            .add(Thief);
+        loader.load(&mut ctx)?;
+        assert!(ctx.names().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn records_the_owner_of_each_item() {
+        let mut ctx = TestContext::default();
+        ctx.register(Item::new("before"));
+        let mut loader = loader(vec![
+            TestPlugin::new("a").registers("a1").registers("a2"),
+            TestPlugin {
+                fail: true,
+                ..TestPlugin::new("b").registers("b").depends_on("a")
+            },
+        ]);
+        assert!(loader.load(&mut ctx).is_err());
+        ctx.register(Item::new("after"));
+
+        let owners: Vec<_> = (0..ctx.registrables().len())
+            .map(|index| ctx.owner(index))
+            .collect();
+        assert_eq!(ctx.names(), ["before", "a1", "a2", "after"]);
+        assert_eq!(owners, [None, Some("a"), Some("a"), None]);
+
+        let a2 = ctx.get_registrables::<Item>()[2];
+        assert_eq!(ctx.owner_of(a2), Some("a"));
+        assert_eq!(ctx.owner_of(&Item::new("a2")), None);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn loads_in_the_same_order_in_parallel() -> Result<()> {
+        let plugins = || {
+            loader(vec![
+                TestPlugin::new("d").registers("d1").depends_on("b"),
+                TestPlugin::new("a").registers("a1").registers("a2"),
+                TestPlugin::new("b").registers("b1"),
+                TestPlugin::new("c").registers("c1"),
+                TestPlugin::new("e").registers("e1").depends_on("a"),
+                TestPlugin::new("f").registers("f1"),
+            ])
+        };
+
+        let mut sequential = TestContext::default();
+        plugins().load(&mut sequential)?;
+        let mut parallel = TestContext::default();
+        plugins().load_parallel(&mut parallel)?;
+
+        assert_eq!(parallel.names(), sequential.names());
+        assert_eq!(parallel, sequential);
+        Ok(())
+    }
+
+    #[test]
+    fn unloading_removes_owned_items() -> Result<()> {
+        let mut ctx = TestContext::default();
+        let mut loader = loader(vec![
+            TestPlugin::new("a").registers("a1"),
+            TestPlugin::new("b").registers("b"),
+            TestPlugin::new("c").registers("c").depends_on("a"),
+        ]);
+        ctx.register(Item::new("host"));
+        loader.load(&mut ctx)?;
+
+        let plugin = loader.unload(&mut ctx, "a")?;
+        assert_eq!(plugin.id(), "a");
+        assert_eq!(ctx.names(), ["host", "b", "c"]);
+        assert_eq!(ctx.owner(2), Some("c"));
+        assert!(loader.plugin("a").is_none());
+
+        let Err(error) = loader.unload(&mut ctx, "a") else {
+            panic!("`a` unloaded twice");
+        };
+        assert_eq!(error.to_string(), "plugin `a` is not loaded");
+        Ok(())
+    }
+
+    #[test]
+    fn reports_registrations_by_kind() -> Result<()> {
+        let mut ctx = TestContext::default();
+        let mut loader = loader(vec![
+            TestPlugin::new("a")
+                .declares("item")
+                .declares("save-files")
+                .registers("x")
+                .registers("y"),
+            TestPlugin::new("b").declares("item"),
+        ]);
+        loader.count_kinds().load(&mut ctx)?;
+
+        let report = loader.report();
+        assert_eq!(report.plugins[0].registrations["item"], 2);
+        assert!(report.plugins[0].warnings.is_empty());
+        assert_eq!(report.plugins[1].warnings, ["registered nothing"]);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_registration_totals_without_counting_kinds() -> Result<()> {
+        let mut ctx = TestContext::default();
+        let mut loader = loader(vec![TestPlugin::new("a").registers("x").registers("y")]);
+        loader.load(&mut ctx)?;
+
+        let report = loader.report();
+        assert!(report.plugins[0].registrations.is_empty());
+        assert_eq!(report.plugins[0].registered(), 2);
+        Ok(())
+    }
+
+    /// Takes the context's items whenever it updates, then fails.
+    struct Flaky;
+
+    impl Plugin<TestContext> for Flaky {
+        fn author(&self) -> &str {
+            "tests"
+        }
+
+        fn id(&self) -> &str {
+            "flaky"
+        }
+
+        fn load(&self, _ctx: &mut TestContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn update(&mut self, ctx: &mut TestContext, _dt: Duration) -> Result<()> {
+            ctx.take_registrables();
+            Err("flaked".into())
+        }
+    }
+
+    #[test]
+    fn stops_updating_a_plugin_after_it_fails() -> Result<()> {
+        let mut ctx = TestContext::default();
+        ctx.register(Item::new("host"));
+        let mut loader = Loader::new();
+        loader
+            .set_grants(Grants::new().grant_all(["item"]))
+            .add(Flaky);
+        loader.load(&mut ctx)?;
+
+        let errors = loader.update(&mut ctx, Duration::ZERO);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(ctx.names(), ["host"]);
+        assert!(loader.update(&mut ctx, Duration::ZERO).is_empty());
+        assert_eq!(
+            loader.update_timing("flaky").map(|timing| timing.calls),
+            Some(1)
+        );
+        assert_eq!(
+            loader.report().plugins[0].warnings,
+            [
+                "registered nothing",
+                "stopped updating after an error: flaked"
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+//! Translated text for plugin content.
+//!
+//! Registrables that implement [`Localized`] name a key their text is looked
+//! up under, such as `crazier-crab.uber-oven`, and keep their own strings as
+//! the text shown when no translation exists. Plugins ship one file per
+//! locale in the `locales` directory of their
+//! [`Plugin::metadata`](crate::Plugin::metadata):
+//!
+//! ```text
+//! # locales/de.lang
+//! crazier-crab.uber-oven.name = Überofen
+//! crazier-crab.uber-oven.description = Erhöht den Ertrag pro Klick um Stufe².
+//! ```
+//!
+//! Each line is a `key = text` pair; blank lines and lines starting with `#`
+//! are skipped, `\n` in a text is a line break and `\\` a backslash.
+//!
+//! The host collects every plugin's files with
+//! [`Loader::load_translations`](crate::loader::Loader::load_translations)
+//! into [`Translations`], which resolves a key in the current locale, then in
+//! its language without the region (`pt` for `pt-BR`), then in each fallback
+//! locale in turn.
+
+use crate::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+pub const FILE_EXTENSION: &str = "lang";
+
+pub trait Localized {
+    /// The prefix of this item's translation keys, such that its `name` is
+    /// looked up under `<key>.name`, or `None` if it is not translated.
+    fn locale_key(&self) -> Option<&str>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Translations {
+    locale: String,
+    fallbacks: Vec<String>,
+    texts: BTreeMap<String, HashMap<String, String>>,
+}
+
+impl Translations {
+    pub fn new(locale: impl Into<String>) -> Self {
+        Self {
+            locale: locale.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Adds a locale to try after the current one, such as the language
+    /// plugins are written in.
+    pub fn with_fallback(mut self, locale: impl Into<String>) -> Self {
+        self.fallbacks.push(locale.into());
+        self
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    pub fn set_locale(&mut self, locale: impl Into<String>) -> &mut Self {
+        self.locale = locale.into();
+        self
+    }
+
+    /// Every locale with at least one text, sorted.
+    pub fn locales(&self) -> Vec<&str> {
+        self.texts.keys().map(String::as_str).collect()
+    }
+
+    /// Adds or replaces the text of `key` in `locale`.
+    pub fn insert(
+        &mut self,
+        locale: impl Into<String>,
+        key: impl Into<String>,
+        text: impl Into<String>,
+    ) -> &mut Self {
+        self.texts
+            .entry(locale.into())
+            .or_default()
+            .insert(key.into(), text.into());
+        self
+    }
+
+    /// Adds every text of a translation file for `locale`. Texts already
+    /// present are replaced.
+    pub fn insert_file(&mut self, locale: &str, contents: &str) -> Result<()> {
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, text)) = line.split_once('=') else {
+                return Err(format!("line {} is not a `key = text` pair", number + 1).into());
+            };
+            self.insert(locale, key.trim(), unescape(text.trim()));
+        }
+        Ok(())
+    }
+
+    /// Reads every `<locale>.lang` file in `dir`.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir).map_err(|err| format!("{}: {err}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == FILE_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let contents = fs::read_to_string(&path)?;
+            self.insert_file(locale, &contents)
+                .map_err(|err| format!("{}: {err}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// The text of `key` in the first locale that has one.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.candidates()
+            .find_map(|locale| self.texts.get(locale)?.get(key))
+            .map(String::as_str)
+    }
+
+    /// The text of `key`, or `default` if no locale has one.
+    pub fn text<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// The text of `item`'s `field`, looked up under `<locale_key>.<field>`,
+    /// or `default` if the item is not translated.
+    pub fn localize<'a, T: Localized>(
+        &'a self,
+        item: &T,
+        field: &str,
+        default: &'a str,
+    ) -> &'a str {
+        match item.locale_key() {
+            Some(key) => self.text(&format!("{key}.{field}"), default),
+            None => default,
+        }
+    }
+
+    /// The locales to try, in order: each requested locale followed by its
+    /// language.
+    fn candidates(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.locale)
+            .chain(&self.fallbacks)
+            .flat_map(|locale| {
+                let language = locale.split_once(['-', '_']).map(|(language, _)| language);
+                std::iter::once(locale.as_str()).chain(language)
+            })
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upgrade(Option<&'static str>);
+
+    impl Localized for Upgrade {
+        fn locale_key(&self) -> Option<&str> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn parses_files() -> Result<()> {
+        let mut translations = Translations::new("de");
+        translations.insert_file("de", "# Kommentar\n\noven.name = Überofen \nmath = a = b\n")?;
+        assert_eq!(translations.get("oven.name"), Some("Überofen"));
+        assert_eq!(translations.get("math"), Some("a = b"));
+
+        let error = translations.insert_file("de", "key\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1 is not a `key = text` pair");
+        Ok(())
+    }
+
+    #[test]
+    fn unescapes_line_breaks_and_backslashes() {
+        assert_eq!(unescape(r"one\ntwo"), "one\ntwo");
+        assert_eq!(unescape(r"back\\slash"), r"back\slash");
+        assert_eq!(unescape(r"\t\"), r"\t\");
+    }
+
+    #[test]
+    fn resolves_region_then_language_then_fallbacks() {
+        let mut translations = Translations::new("pt-BR").with_fallback("en");
+        translations
+            .insert("pt-BR", "a", "pt-BR")
+            .insert("pt", "a", "pt")
+            .insert("pt", "b", "pt")
+            .insert("en", "a", "en")
+            .insert("en", "b", "en")
+            .insert("en", "c", "en");
+
+        assert_eq!(translations.get("a"), Some("pt-BR"));
+        assert_eq!(translations.get("b"), Some("pt"));
+        assert_eq!(translations.get("c"), Some("en"));
+        assert_eq!(translations.get("d"), None);
+        assert_eq!(translations.text("d", "default"), "default");
+        assert_eq!(translations.locales(), ["en", "pt", "pt-BR"]);
+    }
+
+    #[test]
+    fn localizes_items_with_a_key() {
+        let mut translations = Translations::new("de");
+        translations.insert("de", "oven.name", "Ofen");
+
+        assert_eq!(
+            translations.localize(&Upgrade(Some("oven")), "name", "Oven"),
+            "Ofen"
+        );
+        assert_eq!(
+            translations.localize(&Upgrade(None), "name", "Oven"),
+            "Oven"
+        );
+    }
+}
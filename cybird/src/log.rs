@@ -0,0 +1,227 @@
+//! Logging through `tracing`, including from native libraries.
+//!
+//! The loader records a `discover` span while it opens a source, a `load`
+//! span for each load, and a `plugin` span carrying the plugin's id and
+//! version around every plugin's `load`. Plugins log with the re-exported
+//! [`tracing`](crate::tracing) macros.
+//!
+//! A `cdylib` has its own copy of `tracing`, whose global subscriber the host
+//! never sets, so its events would go nowhere. When a native plugin is
+//! opened, the host passes [`host_log`] to the library's `install_logger`
+//! export, which installs a [`Bridge`] as the library's subscriber. The
+//! bridge hands every event back to the host, where it is emitted under the
+//! `cybird::plugin` target inside the current `plugin` span.
+
+use std::ffi::{CStr, CString};
+use std::fmt::{self, Write};
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record as SpanRecord};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// An event crossing from a library to the host.
+#[repr(C)]
+pub struct Record {
+    /// 1 for `ERROR` up to 5 for `TRACE`.
+    pub level: u8,
+    /// The module the event was emitted from.
+    pub target: *const c_char,
+    /// The message followed by any other fields as `key=value`.
+    pub message: *const c_char,
+}
+
+pub type LogFn = unsafe extern "C" fn(*const Record);
+
+/// Emits a library's event through the host's subscriber.
+///
+/// # Safety
+///
+/// `record` must point to a valid [`Record`] whose strings are
+/// nul-terminated.
+pub unsafe extern "C" fn host_log(record: *const Record) {
+    let record = unsafe { &*record };
+    let module = unsafe { CStr::from_ptr(record.target) }.to_string_lossy();
+    let message = unsafe { CStr::from_ptr(record.message) }.to_string_lossy();
+
+    macro_rules! forward {
+        ($level:expr) => {
+            tracing::event!(target: "cybird::plugin", $level, module = %module, "{message}")
+        };
+    }
+    match record.level {
+        1 => forward!(Level::ERROR),
+        2 => forward!(Level::WARN),
+        3 => forward!(Level::INFO),
+        4 => forward!(Level::DEBUG),
+        _ => forward!(Level::TRACE),
+    }
+}
+
+/// The host's current maximum level, encoded like [`Record::level`], with 0
+/// meaning logging is off.
+pub fn max_level() -> u8 {
+    match LevelFilter::current().into_level() {
+        Some(level) => encode(&level),
+        None => 0,
+    }
+}
+
+/// Installs a [`Bridge`] to `log` as this library's global subscriber. Does
+/// nothing if a subscriber is already set.
+pub fn install_bridge(log: LogFn, max_level: u8) {
+    let _ = tracing::subscriber::set_global_default(Bridge::new(log, max_level));
+}
+
+/// A subscriber that forwards events to the host through a [`LogFn`].
+///
+/// Spans opened inside the library are not forwarded; events are nested in
+/// whatever span the host has entered.
+pub struct Bridge {
+    log: LogFn,
+    max_level: u8,
+    next_span: AtomicU64,
+}
+
+impl Bridge {
+    pub fn new(log: LogFn, max_level: u8) -> Self {
+        Self {
+            log,
+            max_level,
+            next_span: AtomicU64::new(1),
+        }
+    }
+}
+
+impl Subscriber for Bridge {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        encode(metadata.level()) <= self.max_level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(match self.max_level {
+            0 => LevelFilter::OFF,
+            1 => LevelFilter::ERROR,
+            2 => LevelFilter::WARN,
+            3 => LevelFilter::INFO,
+            4 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        })
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _: &Id, _: &SpanRecord<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+
+        let target = CString::new(event.metadata().target()).unwrap_or_default();
+        let message = CString::new(fields.message + fields.rest.as_str()).unwrap_or_default();
+        let record = Record {
+            level: encode(event.metadata().level()),
+            target: target.as_ptr(),
+            message: message.as_ptr(),
+        };
+        unsafe { (self.log)(&record) };
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => _ = write!(self.message, "{value:?}"),
+            name => _ = write!(self.rest, " {name}={value:?}"),
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message.push_str(value),
+            name => _ = write!(self.rest, " {name}={value}"),
+        }
+    }
+}
+
+fn encode(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static RECORDS: RefCell<Vec<(u8, String, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    unsafe extern "C" fn capture(record: *const Record) {
+        let record = unsafe { &*record };
+        let target = unsafe { CStr::from_ptr(record.target) }.to_string_lossy();
+        let message = unsafe { CStr::from_ptr(record.message) }.to_string_lossy();
+        RECORDS.with_borrow_mut(|records| {
+            records.push((record.level, target.into_owned(), message.into_owned()))
+        });
+    }
+
+    /// Runs `f` with a bridge to [`capture`] and returns what it captured.
+    fn bridged(max_level: u8, f: impl FnOnce()) -> Vec<(u8, String, String)> {
+        RECORDS.with_borrow_mut(Vec::clear);
+        tracing::subscriber::with_default(Bridge::new(capture, max_level), f);
+        RECORDS.take()
+    }
+
+    #[test]
+    fn forwards_events_with_their_fields() {
+        let records = bridged(5, || {
+            tracing::warn!(target: "plugin::shop", count = 2, name = "oven", "sold {}", 3);
+        });
+        assert_eq!(
+            records,
+            [(
+                2,
+                "plugin::shop".to_string(),
+                "sold 3 count=2 name=oven".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn skips_events_above_the_max_level() {
+        let records = bridged(3, || {
+            tracing::error!("error");
+            tracing::info!("info");
+            tracing::debug!("debug");
+            tracing::trace!("trace");
+        });
+        let messages: Vec<&str> = records
+            .iter()
+            .map(|(_, _, message)| message.as_str())
+            .collect();
+        assert_eq!(messages, ["error", "info"]);
+        assert!(bridged(0, || tracing::error!("error")).is_empty());
+    }
+}
@@ -0,0 +1,897 @@
+//! Plugins loaded from dynamic libraries.
+//!
+//! A plugin library exports a plugin table through `cybird_plugins`, with one
+//! [`PluginEntry`] for every plugin it contains. The entries may be built
+//! for different contexts: each names its context and describes its layout,
+//! and a host only loads the entries built for its own.
+//!
+//! Libraries built with the `dynamic` feature export the table and the other
+//! functions a host looks up, listing every `#[cybird::plugin]` compiled into
+//! them. So does any other binary built with the feature, hosts included,
+//! which is harmless but lists whatever plugins were linked into it.
+//!
+//! Opening a library creates one instance of every plugin in it through
+//! [`Create`]. The instance handles every call for that plugin until the
+//! [`NativePlugin`] is dropped, which destroys it again.
+
+use crate::asset::Asset;
+use crate::{Context, Create, Metadata, Plugin, Result};
+use libloading::{Library, Symbol};
+use std::any::type_name;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString, c_void};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The version of [`PluginEntry`] and the calls behind it. A host only loads
+/// plugins built with the same version.
+pub const ABI_VERSION: u32 = 3;
+
+type GetPlugins = unsafe extern "C" fn() -> PluginTable;
+type FreeString = unsafe extern "C" fn(*mut c_char);
+type InstallLogger = unsafe extern "C" fn(crate::log::LogFn, u8);
+
+/// Appends bytes to the buffer behind the pointer.
+pub type WriteBytes = unsafe extern "C" fn(*mut c_void, *const u8, usize);
+
+/// The plugins a library exports, as returned by its `cybird_plugins`
+/// export.
+#[repr(C)]
+pub struct PluginTable {
+    pub len: usize,
+    pub entries: *const PluginEntry,
+}
+
+/// One plugin in a [`PluginTable`]. Every function but `create` takes the
+/// instance `create` returned.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PluginEntry {
+    /// Creates an instance from the configuration, which may be null. On
+    /// failure, returns null and stores an error message in the second
+    /// argument.
+    pub create: unsafe extern "C" fn(*const c_char, *mut *mut c_char) -> *mut c_void,
+    pub destroy: unsafe extern "C" fn(*mut c_void),
+    /// Stores a field of the plugin such as `id` or `tags` in the third
+    /// argument, or null if the plugin leaves it empty. `context` names the
+    /// context type the plugin was built for, and `abi` describes its
+    /// layout, see [`ABI_VERSION`]. Returns 0 on success, -1 if the field
+    /// cannot be described, with an error message stored instead, and -2 if
+    /// the plugin panicked.
+    pub describe: unsafe extern "C" fn(*const c_void, *const c_char, *mut *mut c_char) -> i32,
+    /// Writes the embedded asset with the given name to the buffer through
+    /// the callback. Returns 0 on success, -1 if there is no such asset and
+    /// -2 if the plugin panicked.
+    pub asset: unsafe extern "C" fn(*const c_void, *const c_char, *mut c_void, WriteBytes) -> i32,
+    /// Loads the plugin into the context behind the pointer. Returns 0 on
+    /// success, -1 on error, with an error message stored in the third
+    /// argument, and -2 if the plugin panicked.
+    pub load: unsafe extern "C" fn(*const c_void, *mut c_void, *mut *mut c_char) -> i32,
+    /// Updates the plugin with the context and the seconds since the last
+    /// tick. Returns and stores errors like `load`.
+    pub update: unsafe extern "C" fn(*mut c_void, *mut c_void, f64, *mut *mut c_char) -> i32,
+    /// Runs the plugin's migrations on a JSON-encoded snapshot state. Only
+    /// present for plugins built with snapshot support.
+    pub migrate: Option<unsafe extern "C" fn(*const c_void, *const c_char) -> *mut c_char>,
+}
+
+impl PluginEntry {
+    /// The entry for plugin `P` built for context `C`. Strings returned by
+    /// the entry are freed with the library's `free_string` export.
+    pub const fn new<C, P>() -> Self
+    where
+        C: Context,
+        P: Plugin<C> + Create + Send + Sync,
+    {
+        Self {
+            create: create::<P>,
+            destroy: destroy::<P>,
+            describe: describe::<C, P>,
+            asset: asset::<C, P>,
+            load: load::<C, P>,
+            update: update::<C, P>,
+            #[cfg(feature = "snapshot")]
+            migrate: Some(migrate::<C, P>),
+            #[cfg(not(feature = "snapshot"))]
+            migrate: None,
+        }
+    }
+}
+
+/// A plugin in the library being built, submitted by `#[cybird::plugin]`.
+/// With the `dynamic` feature, the library exports every submitted plugin
+/// in its plugin table.
+#[doc(hidden)]
+pub struct ExportedPlugin(pub PluginEntry);
+
+inventory::collect!(ExportedPlugin);
+
+/// The exports a host looks up in a plugin library. They are defined here
+/// once, so a library may contain any number of plugins.
+#[cfg(feature = "dynamic")]
+mod exports {
+    use super::{ExportedPlugin, PluginEntry, PluginTable};
+    use std::ffi::CString;
+    use std::os::raw::c_char;
+    use std::sync::OnceLock;
+
+    static PLUGINS: OnceLock<Vec<PluginEntry>> = OnceLock::new();
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn cybird_plugins() -> PluginTable {
+        let plugins = PLUGINS.get_or_init(|| {
+            inventory::iter::<ExportedPlugin>
+                .into_iter()
+                .map(|plugin| plugin.0)
+                .collect()
+        });
+        PluginTable {
+            len: plugins.len(),
+            entries: plugins.as_ptr(),
+        }
+    }
+
+    /// Routes this library's `tracing` events to the host's subscriber.
+    #[unsafe(no_mangle)]
+    pub extern "C" fn install_logger(log: crate::log::LogFn, max_level: u8) {
+        crate::log::install_bridge(log, max_level);
+    }
+
+    /// Frees a string the library returned to the host.
+    ///
+    /// # Safety
+    ///
+    /// `s` must be null or a string returned by one of the library's plugin
+    /// entries, and must not be used afterwards.
+    #[unsafe(no_mangle)]
+    pub unsafe extern "C" fn free_string(s: *mut c_char) {
+        if !s.is_null() {
+            drop(unsafe { CString::from_raw(s) });
+        }
+    }
+}
+
+unsafe extern "C" fn create<P: Create>(
+    config: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut c_void {
+    let config = (!config.is_null()).then(|| unsafe { CStr::from_ptr(config) }.to_string_lossy());
+    let message = match panic::catch_unwind(|| P::create(config.as_deref())) {
+        Ok(Ok(plugin)) => return Box::into_raw(Box::new(plugin)) as *mut c_void,
+        Ok(Err(error)) => error.to_string(),
+        Err(_) => "plugin constructor panicked".to_string(),
+    };
+    unsafe { *error = into_raw(message) };
+    std::ptr::null_mut()
+}
+
+unsafe extern "C" fn destroy<P>(instance: *mut c_void) {
+    drop(unsafe { Box::from_raw(instance as *mut P) });
+}
+
+unsafe extern "C" fn describe<C: Context, P: Plugin<C>>(
+    instance: *const c_void,
+    field: *const c_char,
+    value: *mut *mut c_char,
+) -> i32 {
+    let plugin = unsafe { &*(instance as *const P) };
+    let field = unsafe { CStr::from_ptr(field) }.to_string_lossy();
+    let (code, described) =
+        match panic::catch_unwind(AssertUnwindSafe(|| read_field(plugin, &field))) {
+            Ok(Ok(described)) => (0, described),
+            Ok(Err(error)) => (-1, Some(error.to_string())),
+            Err(_) => (-2, None),
+        };
+    unsafe { *value = described.map_or(std::ptr::null_mut(), into_raw) };
+    code
+}
+
+unsafe extern "C" fn asset<C: Context, P: Plugin<C>>(
+    instance: *const c_void,
+    name: *const c_char,
+    buffer: *mut c_void,
+    write: WriteBytes,
+) -> i32 {
+    let plugin = unsafe { &*(instance as *const P) };
+    let name = unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    let Ok(assets) = panic::catch_unwind(AssertUnwindSafe(|| plugin.assets())) else {
+        return -2;
+    };
+    match assets.iter().find(|(asset, _)| *asset == name) {
+        Some((_, Asset::Embedded(bytes))) => {
+            unsafe { write(buffer, bytes.as_ptr(), bytes.len()) };
+            0
+        }
+        _ => -1,
+    }
+}
+
+unsafe extern "C" fn write_bytes(buffer: *mut c_void, bytes: *const u8, len: usize) {
+    let buffer = unsafe { &mut *(buffer as *mut Vec<u8>) };
+    buffer.extend_from_slice(unsafe { std::slice::from_raw_parts(bytes, len) });
+}
+
+unsafe extern "C" fn load<C: Context, P: Plugin<C>>(
+    instance: *const c_void,
+    ctx: *mut c_void,
+    error: *mut *mut c_char,
+) -> i32 {
+    if ctx.is_null() {
+        unsafe { *error = into_raw("no context to load into".to_string()) };
+        return -1;
+    }
+    let plugin = unsafe { &*(instance as *const P) };
+    let ctx = unsafe { &mut *(ctx as *mut C) };
+
+    // Panics must not unwind across the FFI boundary
+    let result = panic::catch_unwind(AssertUnwindSafe(|| plugin.load(ctx)));
+    unsafe { returned(result, error) }
+}
+
+unsafe extern "C" fn update<C: Context, P: Plugin<C>>(
+    instance: *mut c_void,
+    ctx: *mut c_void,
+    dt: f64,
+    error: *mut *mut c_char,
+) -> i32 {
+    if ctx.is_null() {
+        unsafe { *error = into_raw("no context to update".to_string()) };
+        return -1;
+    }
+    let plugin = unsafe { &mut *(instance as *mut P) };
+    let ctx = unsafe { &mut *(ctx as *mut C) };
+    let dt = Duration::try_from_secs_f64(dt).unwrap_or_default();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| plugin.update(ctx, dt)));
+    unsafe { returned(result, error) }
+}
+
+/// The code for a caught plugin call, storing the message of an error in
+/// `error`.
+unsafe fn returned(result: std::thread::Result<Result<()>>, error: *mut *mut c_char) -> i32 {
+    match result {
+        Ok(Ok(())) => 0,
+        Ok(Err(message)) => {
+            unsafe { *error = into_raw(message.to_string()) };
+            -1
+        }
+        Err(_) => -2,
+    }
+}
+
+#[cfg(feature = "snapshot")]
+unsafe extern "C" fn migrate<C: Context, P: Plugin<C>>(
+    instance: *const c_void,
+    state: *const c_char,
+) -> *mut c_char {
+    let plugin = unsafe { &*(instance as *const P) };
+    let state = unsafe { CStr::from_ptr(state) }.to_string_lossy();
+    into_raw(crate::snapshot::migrate_json::<C>(plugin, &state))
+}
+
+fn into_raw(value: String) -> *mut c_char {
+    CString::new(value)
+        .unwrap_or_else(|error| {
+            let end = error.nul_position();
+            CString::new(&error.into_vec()[..end]).expect("truncated at the first nul")
+        })
+        .into_raw()
+}
+
+/// What a host and a plugin must agree on to share the context `C`: the
+/// [`ABI_VERSION`], the cybird version, and the size and alignment of `C`
+/// and of its registrables.
+fn abi<C: Context>() -> String {
+    format!(
+        "abi {ABI_VERSION}, cybird {}, context {}/{}, registrable {}/{}",
+        env!("CARGO_PKG_VERSION"),
+        size_of::<C>(),
+        align_of::<C>(),
+        size_of::<C::Registrable>(),
+        align_of::<C::Registrable>(),
+    )
+}
+
+/// Reads a field of `plugin` for [`PluginEntry::describe`]. List fields are
+/// joined with newlines, and empty fields are `None`. Assets are listed as
+/// `name<TAB>path` for files and `name` for embedded assets. Fails for list
+/// entries, asset names and paths that would be split apart again.
+fn read_field<C: Context>(plugin: &dyn Plugin<C>, field: &str) -> Result<Option<String>> {
+    let value = match field {
+        "context" => type_name::<C>().to_string(),
+        "abi" => abi::<C>(),
+        "author" => plugin.author().to_string(),
+        "id" => plugin.id().to_string(),
+        "version" => plugin.version().to_string(),
+        "dependencies" => join_lines(field, plugin.dependencies())?,
+        "name" => plugin.metadata().name.clone().unwrap_or_default(),
+        "description" => plugin.metadata().description.clone().unwrap_or_default(),
+        "license" => plugin.metadata().license.clone().unwrap_or_default(),
+        "homepage" => plugin.metadata().homepage.clone().unwrap_or_default(),
+        "tags" => join_lines(field, &plugin.metadata().tags)?,
+        "capabilities" => join_lines(field, &plugin.metadata().capabilities)?,
+        "icon" => plugin
+            .metadata()
+            .icon
+            .as_ref()
+            .map(|icon| icon.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        "locales" => plugin
+            .metadata()
+            .locales
+            .as_ref()
+            .map(|locales| locales.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        "assets" => {
+            let mut assets = Vec::new();
+            for (name, asset) in plugin.assets() {
+                let path = asset.path().map(|path| path.to_string_lossy());
+                if name.contains('\t') || path.as_ref().is_some_and(|path| path.contains('\t')) {
+                    return Err(format!("asset `{name}` has a tab in its name or path").into());
+                }
+                assets.push(match path {
+                    Some(path) => format!("{name}\t{path}"),
+                    None => name.to_string(),
+                });
+            }
+            join_lines(field, assets)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(value).filter(|value| !value.is_empty()))
+}
+
+/// Joins the entries of a list field with newlines, failing for entries
+/// that hold one themselves.
+fn join_lines<I, S>(field: &str, entries: I) -> Result<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut lines = Vec::new();
+    for entry in entries {
+        let entry = entry.as_ref();
+        if entry.contains(['\n', '\r']) {
+            return Err(format!(
+                "{field} entry `{}` has a line break",
+                entry.escape_default()
+            )
+            .into());
+        }
+        lines.push(entry.to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// A plugin from a `cdylib` built with `#[cybird::plugin]`.
+///
+/// The library stays loaded for as long as any plugin opened from it is
+/// alive, so they must outlive anything the plugins registered.
+pub struct NativePlugin {
+    path: PathBuf,
+    context: String,
+    abi: String,
+    author: String,
+    id: String,
+    version: String,
+    dependencies: Vec<String>,
+    metadata: Metadata,
+    /// Embedded assets, copied out of the library when it is opened and
+    /// shared with every [`Asset`] handed out.
+    embedded: BTreeMap<String, Arc<[u8]>>,
+    instance: *mut c_void,
+    entry: &'static PluginEntry,
+    free_string: FreeString,
+    // Dropped after the instance is destroyed
+    _library: Arc<Library>,
+    /// The verified copy the library was loaded from, deleted after it is
+    /// unloaded.
+    _copy: Option<Arc<dyn Send + Sync>>,
+}
+
+// Plugin instances are `Send + Sync`, which `PluginEntry::new` requires.
+unsafe impl Send for NativePlugin {}
+unsafe impl Sync for NativePlugin {}
+
+impl NativePlugin {
+    /// Loads the library at `path` and creates every plugin in its table,
+    /// whatever context they were built for.
+    ///
+    /// If there is a `<library>.config` file next to the library, such as
+    /// `libcrazier_crab.so.config`, its contents are passed to every plugin's
+    /// constructor.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialisers, and the library must have
+    /// been built with the same compiler and against the same versions of
+    /// cybird and of the context types it names. Only part of this is
+    /// checked, see [`NativePlugin::is_for`].
+    pub unsafe fn open_all(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        unsafe { Self::open_all_from(path, path) }
+    }
+
+    /// Like [`NativePlugin::open_all`], loading the copy [`Trust::verify`]
+    /// made. The config file and relative paths are still looked up next to
+    /// the original library, and the copy is deleted once the last plugin
+    /// opened from it is dropped.
+    ///
+    /// # Safety
+    ///
+    /// See [`NativePlugin::open_all`].
+    ///
+    /// [`Trust::verify`]: crate::trust::Trust::verify
+    #[cfg(feature = "trust")]
+    pub unsafe fn open_verified(library: crate::trust::VerifiedLibrary) -> Result<Vec<Self>> {
+        let mut plugins = unsafe { Self::open_all_from(library.original(), library.copy())? };
+        let copy: Arc<dyn Send + Sync> = Arc::new(library);
+        for plugin in &mut plugins {
+            plugin._copy = Some(copy.clone());
+        }
+        Ok(plugins)
+    }
+
+    /// Loads the library file at `library` as if it were at `path`.
+    pub(crate) unsafe fn open_all_from(path: &Path, library: &Path) -> Result<Vec<Self>> {
+        let config = match std::fs::read_to_string(config_path(path)) {
+            Ok(config) => Some(config),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => None,
+            Err(error) => return Err(error.into()),
+        };
+        unsafe { Self::open_from(path, library, config.as_deref()) }
+    }
+
+    /// Like [`NativePlugin::open_all`], passing `config` to every plugin's
+    /// constructor instead of reading a config file.
+    ///
+    /// # Safety
+    ///
+    /// See [`NativePlugin::open_all`].
+    pub unsafe fn open_all_with_config(
+        path: impl AsRef<Path>,
+        config: Option<&str>,
+    ) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        unsafe { Self::open_from(path, path, config) }
+    }
+
+    unsafe fn open_from(path: &Path, library: &Path, config: Option<&str>) -> Result<Vec<Self>> {
+        let library = unsafe { Library::new(library)? };
+        if let Ok(install_logger) = unsafe { library.get::<InstallLogger>(b"install_logger") } {
+            unsafe { install_logger(crate::log::host_log, crate::log::max_level()) };
+        }
+
+        let free_string = unsafe { *library.get::<FreeString>(b"free_string")? };
+        let table = unsafe {
+            let get_plugins: Symbol<GetPlugins> = library.get(b"cybird_plugins")?;
+            get_plugins()
+        };
+        // The table is a static in the library, which the plugins keep loaded
+        let entries: &'static [PluginEntry] = match table.len {
+            0 => &[],
+            len => unsafe { std::slice::from_raw_parts(table.entries, len) },
+        };
+
+        let config = config.map(CString::new).transpose()?;
+        let config = config
+            .as_ref()
+            .map_or(std::ptr::null(), |config| config.as_ptr());
+        let library = Arc::new(library);
+        entries
+            .iter()
+            .map(|entry| unsafe { Self::create(path, entry, config, free_string, library.clone()) })
+            .collect()
+    }
+
+    /// Loads a library that exports exactly one plugin.
+    ///
+    /// # Safety
+    ///
+    /// See [`NativePlugin::open_all`].
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut plugins = unsafe { Self::open_all(path)? };
+        match plugins.len() {
+            1 => Ok(plugins.remove(0)),
+            len => Err(format!("{} exports {len} plugins, expected one", path.display()).into()),
+        }
+    }
+
+    unsafe fn create(
+        path: &Path,
+        entry: &'static PluginEntry,
+        config: *const c_char,
+        free_string: FreeString,
+        library: Arc<Library>,
+    ) -> Result<Self> {
+        let mut error = std::ptr::null_mut();
+        let instance = unsafe { (entry.create)(config, &mut error) };
+        if instance.is_null() {
+            let error = unsafe { take_string(free_string, error)? };
+            let error = error.unwrap_or_else(|| "unknown error".to_string());
+            return Err(format!("could not create plugin in {}: {error}", path.display()).into());
+        }
+
+        let mut plugin = Self {
+            path: path.to_path_buf(),
+            context: String::new(),
+            abi: String::new(),
+            author: String::new(),
+            id: String::new(),
+            version: String::new(),
+            dependencies: Vec::new(),
+            metadata: Metadata::default(),
+            embedded: BTreeMap::new(),
+            instance,
+            entry,
+            free_string,
+            _library: library,
+            _copy: None,
+        };
+        // Dropping `plugin` on an error below destroys the instance again
+        plugin.describe()?;
+        Ok(plugin)
+    }
+
+    /// Reads the plugin's fields from its instance.
+    fn describe(&mut self) -> Result<()> {
+        let field = |name: &str| -> Result<Option<String>> {
+            let c_name = CString::new(name)?;
+            let mut value = std::ptr::null_mut();
+            let (code, value) = unsafe {
+                let code = (self.entry.describe)(self.instance, c_name.as_ptr(), &mut value);
+                (code, take_string(self.free_string, value)?)
+            };
+            let path = self.path.display();
+            match code {
+                0 => Ok(value),
+                -2 => Err(format!("plugin in {path} panicked describing its {name}").into()),
+                _ => Err(format!(
+                    "plugin in {path} has an invalid {name}: {}",
+                    value.unwrap_or_default()
+                )
+                .into()),
+            }
+        };
+        let required = |name: &str| -> Result<String> {
+            field(name)?
+                .ok_or_else(|| format!("plugin in {} has no {name}", self.path.display()).into())
+        };
+        let lines = |name: &str| -> Result<Vec<String>> {
+            Ok(match field(name)? {
+                Some(value) => value.lines().map(str::to_string).collect(),
+                None => Vec::new(),
+            })
+        };
+
+        let relative = |path: String| match self.path.parent() {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        let mut assets = BTreeMap::new();
+        let mut embedded = BTreeMap::new();
+        for line in lines("assets")? {
+            match line.split_once('\t') {
+                Some((name, path)) => {
+                    assets.insert(name.to_string(), relative(path.to_string()));
+                }
+                None => {
+                    let bytes = self.embedded_asset(&line)?;
+                    embedded.insert(line, bytes.into());
+                }
+            }
+        }
+
+        let metadata = Metadata {
+            name: field("name")?,
+            description: field("description")?,
+            license: field("license")?,
+            homepage: field("homepage")?,
+            tags: lines("tags")?,
+            capabilities: lines("capabilities")?,
+            icon: field("icon")?.map(relative),
+            locales: field("locales")?.map(relative),
+            assets,
+        };
+
+        self.context = required("context")?;
+        // Libraries from before the ABI was described never match
+        self.abi = field("abi")?.unwrap_or_default();
+        self.author = required("author")?;
+        self.id = required("id")?;
+        self.version = field("version")?.unwrap_or_else(|| "0.0.0".to_string());
+        self.dependencies = lines("dependencies")?;
+        self.metadata = metadata;
+        self.embedded = embedded;
+        Ok(())
+    }
+
+    fn embedded_asset(&self, name: &str) -> Result<Vec<u8>> {
+        let c_name = CString::new(name)?;
+        let mut bytes = Vec::new();
+        let code = unsafe {
+            (self.entry.asset)(
+                self.instance,
+                c_name.as_ptr(),
+                &mut bytes as *mut Vec<u8> as *mut c_void,
+                write_bytes,
+            )
+        };
+        match code {
+            0 => Ok(bytes),
+            -2 => Err(format!(
+                "plugin in {} panicked reading asset `{name}`",
+                self.path.display()
+            )
+            .into()),
+            _ => Err(format!("plugin in {} has no asset `{name}`", self.path.display()).into()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The name of the context type the plugin was built for.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// The layout of the context the plugin was built for, and the versions
+    /// it was built with.
+    pub fn abi(&self) -> &str {
+        &self.abi
+    }
+
+    /// Whether the plugin was built for the context `C`: the type names
+    /// match, and so do the [`ABI_VERSION`], the cybird version and the size
+    /// and alignment of `C` and its registrables.
+    ///
+    /// This catches libraries built against an older host, but not every
+    /// mismatch. The compiler version and the layout of the types inside the
+    /// context are not compared, which is left to the safety contract of
+    /// [`NativePlugin::open_all`].
+    pub fn is_for<C: Context>(&self) -> bool {
+        self.context == type_name::<C>() && self.abi == abi::<C>()
+    }
+}
+
+impl<C: Context> Plugin<C> for NativePlugin {
+    fn author(&self) -> &str {
+        &self.author
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn dependencies(&self) -> Vec<&str> {
+        self.dependencies.iter().map(String::as_str).collect()
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The metadata exported by the library, with the icon, translations and
+    /// file asset paths relative to the library's directory.
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Borrowed(&self.metadata)
+    }
+
+    /// File assets relative to the library's directory, and the embedded
+    /// ones, copied out of the library once when it was opened.
+    fn assets(&self) -> Vec<(&str, Asset)> {
+        let mut assets = self.metadata.file_assets();
+        for (name, bytes) in &self.embedded {
+            assets.push((name, Asset::Embedded(bytes.clone())));
+        }
+        assets
+    }
+
+    /// Runs the plugin's `migrate` entry, which is only generated for plugins
+    /// built with snapshot support. Without it, only state saved by the
+    /// current version can be restored.
+    #[cfg(feature = "snapshot")]
+    fn migrate(&self, state: &mut crate::snapshot::PluginState) -> Result<()> {
+        use crate::snapshot::{PluginState, migrate};
+
+        let Some(migrate_state) = self.entry.migrate else {
+            return migrate(state, &self.version, &[]);
+        };
+
+        let input = CString::new(serde_json::to_string(state)?)?;
+        let output = unsafe {
+            let ptr = migrate_state(self.instance, input.as_ptr());
+            take_string(self.free_string, ptr)?.unwrap_or_default()
+        };
+
+        let migrated: std::result::Result<PluginState, String> = serde_json::from_str(&output)?;
+        *state = migrated?;
+        Ok(())
+    }
+
+    fn load(&self, ctx: &mut C) -> Result<()> {
+        self.check_context::<C>()?;
+        let ctx = ctx as *mut C as *mut c_void;
+        let mut error = std::ptr::null_mut();
+        let result = unsafe { (self.entry.load)(self.instance, ctx, &mut error) };
+        self.result("load", result, error)
+    }
+
+    fn update(&mut self, ctx: &mut C, dt: Duration) -> Result<()> {
+        self.check_context::<C>()?;
+        let ctx = ctx as *mut C as *mut c_void;
+        let mut error = std::ptr::null_mut();
+        let result =
+            unsafe { (self.entry.update)(self.instance, ctx, dt.as_secs_f64(), &mut error) };
+        self.result("update", result, error)
+    }
+}
+
+impl NativePlugin {
+    fn check_context<C: Context>(&self) -> Result<()> {
+        if self.is_for::<C>() {
+            return Ok(());
+        }
+        if self.context != type_name::<C>() {
+            return Err(format!(
+                "plugin `{}` was built for `{}`, not `{}`",
+                self.id,
+                self.context,
+                type_name::<C>()
+            )
+            .into());
+        }
+        Err(format!(
+            "plugin `{}` was built with {}, but the host has {}",
+            self.id,
+            match self.abi.as_str() {
+                "" => "no ABI description",
+                abi => abi,
+            },
+            abi::<C>()
+        )
+        .into())
+    }
+
+    /// The result of a call that returned `code`, with the error message
+    /// the library stored in `error`, if any.
+    fn result(&self, call: &str, code: i32, error: *mut c_char) -> Result<()> {
+        let error = unsafe { take_string(self.free_string, error)? };
+        match (code, error) {
+            (0, _) => Ok(()),
+            (-2, _) => Err(format!("plugin `{}` panicked", self.id).into()),
+            (_, Some(error)) => Err(error.into()),
+            (code, None) => {
+                Err(format!("plugin `{}` failed to {call} (code {code})", self.id).into())
+            }
+        }
+    }
+}
+
+impl Drop for NativePlugin {
+    fn drop(&mut self) {
+        unsafe { (self.entry.destroy)(self.instance) };
+    }
+}
+
+/// The config file read by [`NativePlugin::open_all`] for the library at
+/// `path`.
+pub fn config_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".config");
+    path.with_file_name(file_name)
+}
+
+/// Copies a string returned by the library and frees it.
+unsafe fn take_string(free_string: FreeString, ptr: *mut c_char) -> Result<Option<String>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    unsafe {
+        let value = CStr::from_ptr(ptr).to_str().map(str::to_string);
+        free_string(ptr);
+        Ok(Some(value?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestContext, TestPlugin};
+
+    #[test]
+    fn describes_the_context_and_its_layout() -> Result<()> {
+        let plugin = TestPlugin::new("a");
+        assert_eq!(
+            read_field::<TestContext>(&plugin, "context")?.as_deref(),
+            Some(type_name::<TestContext>())
+        );
+        let abi = read_field::<TestContext>(&plugin, "abi")?.unwrap_or_default();
+        assert!(abi.starts_with(&format!("abi {ABI_VERSION}, cybird ")));
+        let layout = format!(
+            "context {}/{}",
+            size_of::<TestContext>(),
+            align_of::<TestContext>()
+        );
+        assert!(abi.contains(&layout));
+        Ok(())
+    }
+
+    #[test]
+    fn passes_load_errors_back() -> Result<()> {
+        let plugin = TestPlugin {
+            fail: true,
+            ..TestPlugin::new("a")
+        };
+        let instance = &plugin as *const TestPlugin as *const c_void;
+        let mut ctx = TestContext::default();
+        let ctx = &mut ctx as *mut TestContext as *mut c_void;
+
+        let mut error = std::ptr::null_mut();
+        let code = unsafe { load::<TestContext, TestPlugin>(instance, ctx, &mut error) };
+        assert_eq!(code, -1);
+        let error = unsafe { CString::from_raw(error) };
+        assert_eq!(error.to_str()?, "a failed");
+        Ok(())
+    }
+
+    /// Fails every update.
+    struct Stalled;
+
+    impl Plugin<TestContext> for Stalled {
+        fn author(&self) -> &str {
+            "tests"
+        }
+
+        fn id(&self) -> &str {
+            "stalled"
+        }
+
+        fn load(&self, _ctx: &mut TestContext) -> Result<()> {
+            Ok(())
+        }
+
+        fn update(&mut self, _ctx: &mut TestContext, _dt: Duration) -> Result<()> {
+            Err("stalled".into())
+        }
+    }
+
+    #[test]
+    fn passes_update_errors_back() -> Result<()> {
+        let mut plugin = Stalled;
+        let instance = &mut plugin as *mut Stalled as *mut c_void;
+        let mut ctx = TestContext::default();
+        let ctx = &mut ctx as *mut TestContext as *mut c_void;
+
+        let mut error = std::ptr::null_mut();
+        let code = unsafe { update::<TestContext, Stalled>(instance, ctx, 0.5, &mut error) };
+        assert_eq!(code, -1);
+        let error = unsafe { CString::from_raw(error) };
+        assert_eq!(error.to_str()?, "stalled");
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_fields_that_would_split_apart() -> Result<()> {
+        let plugin = TestPlugin::new("a")
+            .declares("item")
+            .declares("save\nfiles");
+        let Err(error) = read_field::<TestContext>(&plugin, "capabilities") else {
+            panic!("a capability with a line break was described");
+        };
+        assert_eq!(
+            error.to_string(),
+            r"capabilities entry `save\nfiles` has a line break"
+        );
+        assert_eq!(read_field::<TestContext>(&plugin, "name")?, None);
+        assert_eq!(read_field::<TestContext>(&plugin, "unknown")?, None);
+        Ok(())
+    }
+}
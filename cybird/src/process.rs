@@ -0,0 +1,413 @@
+//! Native plugins run in a helper process.
+//!
+//! A [`ProcessPlugin`] opens its library in a child process instead of the
+//! host, so a plugin that crashes only takes the helper down with it. The
+//! helper is the host's own executable, started again with the
+//! `CYBIRD_PLUGIN_*` environment variables set, which is why hosts must call
+//! [`run_helper`] first thing in `main`. The helper loads the plugin into an
+//! empty context and sends the registrations back as JSON over a local socket,
+//! where they are registered into the host's context.
+//!
+//! The helper proves it is the process the host started by sending a random
+//! token, passed in its environment, ahead of its response; connections that
+//! do not send it within [`TOKEN_TIMEOUT`] are dropped. A helper that has not
+//! responded within its timeout is killed.
+
+use crate::asset::Asset;
+use crate::native::NativePlugin;
+use crate::{Context, Metadata, Plugin, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a helper may take to respond unless
+/// [`ProcessPlugin::with_timeout`] says otherwise.
+pub const HELPER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a connection to the host's socket may take to send the helper's
+/// token. The helper connects only once its response is ready, so anything
+/// slower is not the helper and is dropped before it can hold up the host.
+pub const TOKEN_TIMEOUT: Duration = Duration::from_secs(1);
+
+const ADDRESS_VAR: &str = "CYBIRD_PLUGIN_ADDRESS";
+const TOKEN_VAR: &str = "CYBIRD_PLUGIN_TOKEN";
+const PATH_VAR: &str = "CYBIRD_PLUGIN_PATH";
+const LIBRARY_VAR: &str = "CYBIRD_PLUGIN_LIBRARY";
+const COMMAND_VAR: &str = "CYBIRD_PLUGIN_COMMAND";
+const ID_VAR: &str = "CYBIRD_PLUGIN_ID";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Description {
+    id: String,
+    author: String,
+    version: String,
+    dependencies: Vec<String>,
+    metadata: Metadata,
+}
+
+/// A native plugin loaded in a helper process.
+///
+/// The helper exits once the plugin has loaded, so neither [`Plugin::update`]
+/// nor [`Plugin::migrate`] is forwarded to it. Saved state of a process
+/// plugin is only restored if it was written by the same version.
+pub struct ProcessPlugin {
+    path: PathBuf,
+    /// The file the helper loads, `path` or a verified copy of it.
+    library: PathBuf,
+    description: Description,
+    timeout: Duration,
+    _copy: Option<Arc<dyn Send + Sync>>,
+}
+
+impl ProcessPlugin {
+    /// Reads the metadata of every plugin in the library at `path` that was
+    /// built for the helper's context, in a helper process.
+    pub fn open_all(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        Self::open_all_from(path, path, None)
+    }
+
+    /// Like [`ProcessPlugin::open_all`], with the helper loading the copy
+    /// [`Trust::verify`] made. The copy is deleted once the last plugin
+    /// opened from it is dropped.
+    ///
+    /// [`Trust::verify`]: crate::trust::Trust::verify
+    #[cfg(feature = "trust")]
+    pub fn open_verified(library: crate::trust::VerifiedLibrary) -> Result<Vec<Self>> {
+        let (path, copy) = (
+            library.original().to_path_buf(),
+            library.copy().to_path_buf(),
+        );
+        Self::open_all_from(&path, &copy, Some(Arc::new(library)))
+    }
+
+    fn open_all_from(
+        path: &Path,
+        library: &Path,
+        copy: Option<Arc<dyn Send + Sync>>,
+    ) -> Result<Vec<Self>> {
+        let descriptions: Vec<Description> = run(path, library, "describe", None, HELPER_TIMEOUT)?;
+        Ok(descriptions
+            .into_iter()
+            .map(|description| Self {
+                path: path.to_path_buf(),
+                library: library.to_path_buf(),
+                description,
+                timeout: HELPER_TIMEOUT,
+                _copy: copy.clone(),
+            })
+            .collect())
+    }
+
+    /// Like [`ProcessPlugin::open_all`], for a library with exactly one
+    /// plugin for the context.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut plugins = Self::open_all(path)?;
+        match plugins.len() {
+            1 => Ok(plugins.remove(0)),
+            len => Err(format!("{} exports {len} plugins, expected one", path.display()).into()),
+        }
+    }
+
+    /// How long the helper may take to load the plugin before it is killed.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl<C> Plugin<C> for ProcessPlugin
+where
+    C: Context,
+    C::Registrable: DeserializeOwned,
+{
+    fn author(&self) -> &str {
+        &self.description.author
+    }
+
+    fn id(&self) -> &str {
+        &self.description.id
+    }
+
+    fn version(&self) -> &str {
+        &self.description.version
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Borrowed(&self.description.metadata)
+    }
+
+    /// The helper's file assets. Embedded assets stay in the helper.
+    fn assets(&self) -> Vec<(&str, Asset)> {
+        self.description.metadata.file_assets()
+    }
+
+    fn dependencies(&self) -> Vec<&str> {
+        let dependencies = &self.description.dependencies;
+        dependencies.iter().map(String::as_str).collect()
+    }
+
+    /// Loads the plugin in a helper process and registers what it registered.
+    ///
+    /// The plugin starts from an empty context, so it cannot see or change
+    /// items registered before it.
+    fn load(&self, ctx: &mut C) -> Result<()> {
+        let registrables: Vec<C::Registrable> = run(
+            &self.path,
+            &self.library,
+            "load",
+            Some(&self.description.id),
+            self.timeout,
+        )?;
+        for registrable in registrables {
+            ctx.register(registrable);
+        }
+        Ok(())
+    }
+}
+
+/// Runs the plugin command this process was started for, if it was started as
+/// a plugin helper, and exits. Returns immediately otherwise.
+///
+/// # Safety
+///
+/// The process's environment must be trusted. When the `CYBIRD_PLUGIN_*`
+/// variables are set, the library they name is opened with
+/// [`NativePlugin::open_all`], whose requirements apply to it.
+pub unsafe fn run_helper<C>()
+where
+    C: Context + Default,
+    C::Registrable: Serialize,
+{
+    let (Ok(address), Ok(token), Ok(path), Ok(library), Ok(command)) = (
+        std::env::var(ADDRESS_VAR),
+        std::env::var(TOKEN_VAR),
+        std::env::var(PATH_VAR),
+        std::env::var(LIBRARY_VAR),
+        std::env::var(COMMAND_VAR),
+    ) else {
+        return;
+    };
+    let (path, library) = (Path::new(&path), Path::new(&library));
+
+    let id = std::env::var(ID_VAR).unwrap_or_default();
+    let response = match command.as_str() {
+        "describe" => respond(describe::<C>(path, library)),
+        "load" => respond(load::<C>(path, library, &id)),
+        command => respond::<()>(Err(format!("unknown helper command `{command}`").into())),
+    };
+
+    let sent = TcpStream::connect(&address).and_then(|mut stream| {
+        stream.write_all(format!("{token}\n").as_bytes())?;
+        stream.write_all(&response)
+    });
+    std::process::exit(if sent.is_ok() { 0 } else { 1 });
+}
+
+fn describe<C: Context>(path: &Path, library: &Path) -> Result<Vec<Description>> {
+    let plugins = unsafe { NativePlugin::open_all_from(path, library)? };
+    Ok(plugins
+        .iter()
+        .filter(|plugin| plugin.is_for::<C>())
+        .map(|plugin| description::<C>(plugin))
+        .collect())
+}
+
+fn description<C: Context>(plugin: &dyn Plugin<C>) -> Description {
+    let strings = |values: Vec<&str>| values.into_iter().map(str::to_string).collect();
+
+    Description {
+        id: plugin.id().to_string(),
+        author: plugin.author().to_string(),
+        version: plugin.version().to_string(),
+        dependencies: strings(plugin.dependencies()),
+        metadata: Metadata {
+            assets: plugin
+                .assets()
+                .into_iter()
+                .filter_map(|(name, asset)| Some((name.to_string(), asset.path()?.to_path_buf())))
+                .collect(),
+            ..plugin.metadata().into_owned()
+        },
+    }
+}
+
+fn load<C: Context + Default>(
+    path: &Path,
+    library: &Path,
+    id: &str,
+) -> Result<Vec<C::Registrable>> {
+    let plugins = unsafe { NativePlugin::open_all_from(path, library)? };
+    let plugin = plugins
+        .iter()
+        .find(|plugin| plugin.is_for::<C>() && Plugin::<C>::id(*plugin) == id)
+        .ok_or_else(|| format!("{} has no plugin `{id}`", path.display()))?;
+    let mut ctx = C::default();
+    plugin.load(&mut ctx)?;
+    Ok(ctx.take_registrables())
+}
+
+fn respond<T: Serialize>(result: Result<T>) -> Vec<u8> {
+    let result = result.map_err(|error| error.to_string());
+    serde_json::to_vec(&result).expect("helper response serializes to JSON")
+}
+
+/// Starts a helper for `command` on the library at `path`, loaded from the
+/// file `library`, and waits up to `timeout` for its response.
+fn run<T: DeserializeOwned>(
+    path: &Path,
+    library: &Path,
+    command: &str,
+    id: Option<&str>,
+    timeout: Duration,
+) -> Result<T> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let token = token()?;
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .env(ADDRESS_VAR, listener.local_addr()?.to_string())
+        .env(TOKEN_VAR, &token)
+        .env(PATH_VAR, path)
+        .env(LIBRARY_VAR, library)
+        .env(COMMAND_VAR, command)
+        .env(ID_VAR, id.unwrap_or_default())
+        .stdin(Stdio::null())
+        .spawn()?;
+
+    let deadline = Instant::now() + timeout;
+    let mut exited = false;
+    let response = loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Some(response) = read_response(stream, &token, deadline) {
+                    break Some(response);
+                }
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                // Accept once more after the helper exits, in case it
+                // connected just before
+                if exited {
+                    break None;
+                }
+                exited = child.try_wait()?.is_some();
+                if !exited && Instant::now() >= deadline {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!(
+                        "plugin helper for {} did not respond within {timeout:?}",
+                        path.display()
+                    )
+                    .into());
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            Err(error) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(error.into());
+            }
+        }
+    };
+
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(format!("plugin helper for {} exited with {status}", path.display()).into());
+    }
+    let Some(response) = response else {
+        return Err(format!(
+            "plugin helper for {} exited without responding",
+            path.display()
+        )
+        .into());
+    };
+
+    let result: std::result::Result<T, String> = serde_json::from_slice(&response)?;
+    Ok(result?)
+}
+
+/// Reads a response from `stream` if it starts with `token`, giving up at
+/// `deadline`, or after [`TOKEN_TIMEOUT`] if the token has not arrived.
+fn read_response(mut stream: TcpStream, token: &str, deadline: Instant) -> Option<Vec<u8>> {
+    let timeout = |limit: Duration| {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        Some(remaining.min(limit).max(Duration::from_millis(1)))
+    };
+    stream.set_nonblocking(false).ok()?;
+
+    stream.set_read_timeout(timeout(TOKEN_TIMEOUT)).ok()?;
+    let expected = format!("{token}\n");
+    let mut received = vec![0; expected.len()];
+    stream.read_exact(&mut received).ok()?;
+    if received != expected.as_bytes() {
+        return None;
+    }
+
+    stream.set_read_timeout(timeout(Duration::MAX)).ok()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).ok()?;
+    Some(response)
+}
+
+/// A random token the helper sends back to identify itself.
+fn token() -> Result<String> {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes).map_err(|error| format!("no random token: {error}"))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(message: &[u8]) -> Result<Option<Vec<u8>>> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let mut client = TcpStream::connect(listener.local_addr()?)?;
+        client.write_all(message)?;
+        drop(client);
+
+        let (stream, _) = listener.accept()?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        Ok(read_response(stream, "secret", deadline))
+    }
+
+    #[test]
+    fn reads_responses_with_the_token() -> Result<()> {
+        assert_eq!(send(b"secret\n{}")?, Some(b"{}".to_vec()));
+        assert_eq!(send(b"guess\n{}")?, None);
+        assert_eq!(send(b"secret{}")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn drops_silent_connections() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let _client = TcpStream::connect(listener.local_addr()?)?;
+
+        let (stream, _) = listener.accept()?;
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(30);
+        assert_eq!(read_response(stream, "secret", deadline), None);
+        assert!(start.elapsed() < TOKEN_TIMEOUT * 5);
+        Ok(())
+    }
+
+    #[test]
+    fn tokens_are_random() -> Result<()> {
+        let token = token()?;
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, super::token()?);
+        Ok(())
+    }
+}
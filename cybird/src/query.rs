@@ -0,0 +1,147 @@
+//! Filtering registrations by tag.
+//!
+//! Registrables that implement [`Tagged`] carry free-form tags such as
+//! `click`, `idle` or `prestige`, which plugins set like any other field.
+//! [`Context::query`](crate::Context::query) collects every registered item
+//! of one type and [`Query::with_tag`] narrows it down, so hosts can group
+//! plugin-provided data without knowing every plugin:
+//!
+//! ```ignore
+//! for tag in ctx.query::<Upgrade>().tags() {
+//!     let tab = ctx.query::<Upgrade>().with_tag(tag);
+//!     // ...
+//! }
+//! ```
+
+use std::collections::BTreeSet;
+
+pub trait Tagged {
+    fn tags(&self) -> Vec<&str>;
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags().contains(&tag)
+    }
+}
+
+/// Registered items of one type, in registration order.
+#[derive(Debug)]
+pub struct Query<'a, T> {
+    items: Vec<&'a T>,
+}
+
+impl<'a, T> Query<'a, T> {
+    pub fn new(items: Vec<&'a T>) -> Self {
+        Self { items }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + '_ {
+        self.items.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn into_vec(self) -> Vec<&'a T> {
+        self.items
+    }
+}
+
+impl<'a, T: Tagged> Query<'a, T> {
+    /// Keeps the items tagged `tag`.
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.items.retain(|item| item.has_tag(tag));
+        self
+    }
+
+    /// Keeps the items without the tag `tag`.
+    pub fn without_tag(mut self, tag: &str) -> Self {
+        self.items.retain(|item| !item.has_tag(tag));
+        self
+    }
+
+    /// Every tag used by the items, sorted and without duplicates.
+    pub fn tags(&self) -> Vec<&'a str> {
+        let tags: BTreeSet<&'a str> = self.items.iter().flat_map(|item| item.tags()).collect();
+        tags.into_iter().collect()
+    }
+}
+
+impl<'a, T> IntoIterator for Query<'a, T> {
+    type Item = &'a T;
+    type IntoIter = std::vec::IntoIter<&'a T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Upgrade {
+        name: &'static str,
+        tags: Vec<&'static str>,
+    }
+
+    impl Tagged for Upgrade {
+        fn tags(&self) -> Vec<&str> {
+            self.tags.clone()
+        }
+    }
+
+    fn upgrades() -> Vec<Upgrade> {
+        let upgrade = |name, tags: &[&'static str]| Upgrade {
+            name,
+            tags: tags.to_vec(),
+        };
+        vec![
+            upgrade("cursor", &["click"]),
+            upgrade("oven", &["idle", "bakery"]),
+            upgrade("golden cursor", &["click", "prestige"]),
+            upgrade("plain", &[]),
+        ]
+    }
+
+    fn names<'a>(query: Query<'a, Upgrade>) -> Vec<&'a str> {
+        query.into_iter().map(|upgrade| upgrade.name).collect()
+    }
+
+    #[test]
+    fn filters_by_tag() {
+        let upgrades = upgrades();
+        let query = || Query::new(upgrades.iter().collect());
+
+        assert_eq!(
+            names(query().with_tag("click")),
+            ["cursor", "golden cursor"]
+        );
+        assert_eq!(
+            names(query().with_tag("click").without_tag("prestige")),
+            ["cursor"]
+        );
+        assert_eq!(names(query().without_tag("click")), ["oven", "plain"]);
+        assert!(query().with_tag("missing").is_empty());
+    }
+
+    #[test]
+    fn lists_tags_sorted_and_once() {
+        let upgrades = upgrades();
+        let query = Query::new(upgrades.iter().collect());
+        assert_eq!(query.tags(), ["bakery", "click", "idle", "prestige"]);
+
+        let untagged = Query::new(
+            upgrades
+                .iter()
+                .filter(|upgrade| upgrade.tags.is_empty())
+                .collect(),
+        );
+        assert_eq!(untagged.len(), 1);
+        assert!(untagged.tags().is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Runtime inspection of registrables through `bevy_reflect`.
+//!
+//! When a context's registrable enum derives `Reflect`, every registered item
+//! can be listed, read and edited by field name without knowing its concrete
+//! type, which is what editors and inspectors need.
+
+use crate::{Context, Result};
+use bevy_reflect::{PartialReflect, Reflect, ReflectMut, ReflectPath, ReflectRef, TypeInfo, Typed};
+
+pub use bevy_reflect;
+
+pub trait ReflectContext: Context {
+    /// Every registered item as a reflected value, unwrapped from its
+    /// registrable enum variant.
+    fn reflect_registrables(&self) -> Vec<&dyn PartialReflect>;
+
+    fn reflect_registrables_mut(&mut self) -> Vec<&mut dyn PartialReflect>;
+
+    /// Registered items whose type path (e.g. `clicker_plugin::Upgrade`) is
+    /// `type_path`.
+    fn query_reflect(&self, type_path: &str) -> Vec<&dyn PartialReflect>;
+
+    fn query_reflect_mut(&mut self, type_path: &str) -> Vec<&mut dyn PartialReflect>;
+
+    /// Type information of every type the registrable enum can hold.
+    fn registrable_types() -> Vec<&'static TypeInfo>;
+}
+
+impl<C> ReflectContext for C
+where
+    C: Context,
+    C::Registrable: Reflect + Typed,
+{
+    fn reflect_registrables(&self) -> Vec<&dyn PartialReflect> {
+        self.registrables()
+            .iter()
+            .map(|registrable| unwrap_variant(registrable.as_partial_reflect()))
+            .collect()
+    }
+
+    fn reflect_registrables_mut(&mut self) -> Vec<&mut dyn PartialReflect> {
+        self.registrables_mut()
+            .iter_mut()
+            .map(|registrable| unwrap_variant_mut(registrable.as_partial_reflect_mut()))
+            .collect()
+    }
+
+    fn query_reflect(&self, type_path: &str) -> Vec<&dyn PartialReflect> {
+        self.reflect_registrables()
+            .into_iter()
+            .filter(|value| value.reflect_type_path() == type_path)
+            .collect()
+    }
+
+    fn query_reflect_mut(&mut self, type_path: &str) -> Vec<&mut dyn PartialReflect> {
+        self.reflect_registrables_mut()
+            .into_iter()
+            .filter(|value| value.reflect_type_path() == type_path)
+            .collect()
+    }
+
+    fn registrable_types() -> Vec<&'static TypeInfo> {
+        match C::Registrable::type_info() {
+            TypeInfo::Enum(info) => info
+                .iter()
+                .filter_map(|variant| variant.as_tuple_variant().ok()?.field_at(0)?.type_info())
+                .collect(),
+            info => vec![info],
+        }
+    }
+}
+
+/// Reads the field at `path` (e.g. `level` or `effects[0].trigger`).
+pub fn field<'a>(value: &'a dyn PartialReflect, path: &str) -> Result<&'a dyn PartialReflect> {
+    path.reflect_element(value)
+        .map_err(|err| err.to_string().into())
+}
+
+/// Overwrites the field at `path` with `new_value`.
+pub fn set_field(
+    value: &mut dyn PartialReflect,
+    path: &str,
+    new_value: &dyn PartialReflect,
+) -> Result<()> {
+    let field = path
+        .reflect_element_mut(value)
+        .map_err(|err| err.to_string())?;
+    field.try_apply(new_value)?;
+    Ok(())
+}
+
+fn unwrap_variant(registrable: &dyn PartialReflect) -> &dyn PartialReflect {
+    match registrable.reflect_ref() {
+        ReflectRef::Enum(variant) => variant.field_at(0).unwrap_or(registrable),
+        _ => registrable,
+    }
+}
+
+fn unwrap_variant_mut(registrable: &mut dyn PartialReflect) -> &mut dyn PartialReflect {
+    let has_payload =
+        matches!(registrable.reflect_ref(), ReflectRef::Enum(variant) if variant.field_len() > 0);
+    if !has_payload {
+        return registrable;
+    }
+
+    match registrable.reflect_mut() {
+        ReflectMut::Enum(variant) => variant.field_at_mut(0).unwrap(),
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::testing::Item;
+    use bevy_reflect::TypePath;
+
+    /// A registrable enum with a unit variant, which has no item to unwrap.
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    enum Entry {
+        Item(Item),
+        Divider,
+    }
+
+    #[derive(Default, Context)]
+    struct Catalog(Vec<Entry>);
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::default();
+        catalog.register(Entry::Item(Item::new("a")));
+        catalog.register(Entry::Divider);
+        catalog.register(Entry::Item(Item::new("b")));
+        catalog
+    }
+
+    #[test]
+    fn unwraps_items_from_their_variants() {
+        let catalog = catalog();
+        let paths: Vec<&str> = catalog
+            .reflect_registrables()
+            .iter()
+            .map(|value| value.reflect_type_path())
+            .collect();
+        assert_eq!(
+            paths,
+            [Item::type_path(), Entry::type_path(), Item::type_path()]
+        );
+        assert_eq!(catalog.query_reflect(Item::type_path()).len(), 2);
+        assert!(catalog.query_reflect("cybird::Missing").is_empty());
+    }
+
+    #[test]
+    fn edits_fields_by_path() -> Result<()> {
+        let mut catalog = catalog();
+        let mut values = catalog.reflect_registrables_mut();
+        assert_eq!(values[1].reflect_type_path(), Entry::type_path());
+        set_field(values[2], "level", &7u32)?;
+        assert_eq!(field(values[2], "level")?.try_downcast_ref(), Some(&7u32));
+        assert!(field(values[2], "missing").is_err());
+        assert!(set_field(values[0], "level", &"seven".to_string()).is_err());
+
+        assert_eq!(
+            catalog.0[2],
+            Entry::Item(Item {
+                level: 7,
+                ..Item::new("b")
+            })
+        );
+        for item in catalog.query_reflect_mut(Item::type_path()) {
+            set_field(item, "name", &"renamed".to_string())?;
+        }
+        assert_eq!(catalog.0[0], Entry::Item(Item::new("renamed")));
+        Ok(())
+    }
+
+    #[test]
+    fn lists_the_types_variants_hold() {
+        let paths: Vec<&str> = Catalog::registrable_types()
+            .iter()
+            .map(|info| info.type_path())
+            .collect();
+        assert_eq!(paths, [Item::type_path()]);
+    }
+}
@@ -0,0 +1,85 @@
+//! A link-time registry of plugins compiled into the host.
+//!
+//! `#[cybird::plugin(context = MyContext)]` submits the plugin here, so a host
+//! that links plugin crates statically can load all of them without naming
+//! each one.
+
+use crate::loader::BoxedPlugin;
+use crate::{Context, Result};
+use std::any::{Any, TypeId};
+
+/// A registry entry, created by `#[cybird::plugin]`.
+#[doc(hidden)]
+pub struct StaticPlugin {
+    pub context: fn() -> TypeId,
+    /// Creates the plugin through [`Create`](crate::Create), without a
+    /// configuration, as a type-erased `BoxedPlugin` for the context.
+    pub create: fn() -> Result<Box<dyn Any>>,
+}
+
+inventory::collect!(StaticPlugin);
+
+/// Creates every compiled-in plugin for context `C`. Plugins whose
+/// constructor fails are logged and left out.
+pub fn static_plugins<C: Context + 'static>() -> Vec<BoxedPlugin<C>> {
+    inventory::iter::<StaticPlugin>
+        .into_iter()
+        .filter(|entry| (entry.context)() == TypeId::of::<C>())
+        .filter_map(|entry| match (entry.create)() {
+            Ok(plugin) => plugin.downcast::<BoxedPlugin<C>>().ok(),
+            Err(error) => {
+                tracing::warn!(%error, "could not create compiled-in plugin");
+                None
+            }
+        })
+        .map(|plugin| *plugin)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::source::{Origin, PluginSource, StaticSource};
+    use crate::testing::{Registrable, TestContext, TestPlugin};
+
+    /// A second context, which none of the entries below are for.
+    #[derive(Default, Context)]
+    struct OtherContext(Vec<Registrable>);
+
+    inventory::submit! {
+        StaticPlugin {
+            context: TypeId::of::<TestContext>,
+            create: || {
+                let plugin: BoxedPlugin<TestContext> = Box::new(TestPlugin::new("static"));
+                Ok(Box::new(plugin))
+            },
+        }
+    }
+
+    inventory::submit! {
+        StaticPlugin {
+            context: TypeId::of::<TestContext>,
+            create: || Err("needs a configuration".into()),
+        }
+    }
+
+    #[test]
+    fn creates_the_plugins_for_the_context() {
+        let ids: Vec<String> = static_plugins::<TestContext>()
+            .iter()
+            .map(|plugin| plugin.id().to_string())
+            .collect();
+        assert_eq!(ids, ["static"]);
+        assert!(static_plugins::<OtherContext>().is_empty());
+    }
+
+    #[test]
+    fn opens_the_registry_as_a_source() {
+        let opened = PluginSource::<TestContext>::open(&StaticSource);
+        assert_eq!(opened.len(), 1);
+        let (origin, plugin) = &opened[0];
+        assert_eq!(*origin, Origin::Static);
+        assert_eq!(plugin.as_ref().unwrap().id(), "static");
+    }
+}
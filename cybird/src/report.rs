@@ -0,0 +1,204 @@
+//! What happened during the most recent load, plugin by plugin.
+//!
+//! [`Loader::report`](crate::loader::Loader::report) builds a [`LoadReport`]
+//! after any of the load methods. It prints as a text table through
+//! [`Display`](fmt::Display) and, with the `json` feature, serializes to
+//! JSON.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct LoadReport {
+    /// Every plugin in the loader, in load order. Plugins the load never
+    /// reached come last, without a position.
+    pub plugins: Vec<PluginReport>,
+    /// Errors that did not belong to a loaded plugin: plugins that could not
+    /// be opened, or an ordering that could not be resolved.
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct PluginReport {
+    pub id: String,
+    pub version: String,
+    /// Where the plugin came from, such as `native library plugins/x.so`.
+    pub origin: String,
+    /// The file or directory the plugin was opened from, if any.
+    pub path: Option<PathBuf>,
+    /// Position in the load order, starting at 0.
+    pub position: Option<usize>,
+    #[cfg_attr(
+        feature = "json",
+        serde(rename = "duration_ms", serialize_with = "millis")
+    )]
+    pub duration: Duration,
+    /// How many items the plugin registered, by kind. Only filled in when the
+    /// loader counts kinds, see
+    /// [`Loader::count_kinds`](crate::loader::Loader::count_kinds).
+    pub registrations: BTreeMap<String, usize>,
+    /// How many items the plugin registered in all.
+    pub(crate) registered: usize,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl LoadReport {
+    /// Whether every plugin loaded without an error.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+            && self
+                .plugins
+                .iter()
+                .all(|plugin| plugin.position.is_some() && plugin.error.is_none())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("load reports always serialize")
+    }
+}
+
+impl PluginReport {
+    /// How many items the plugin registered, whether or not the loader counts
+    /// them by kind.
+    pub fn registered(&self) -> usize {
+        self.registered
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for plugin in &self.plugins {
+            writeln!(f, "{plugin}")?;
+        }
+        for error in &self.errors {
+            writeln!(f, "error: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PluginReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{:>3}. ", position + 1)?,
+            None => write!(f, "   - ")?,
+        }
+        write!(f, "{} {} ({})", self.id, self.version, self.origin)?;
+
+        match (&self.error, self.position) {
+            (Some(_), _) => write!(f, " failed after {:.2?}", self.duration)?,
+            (None, Some(_)) => write!(f, " in {:.2?}", self.duration)?,
+            (None, None) => write!(f, " not loaded")?,
+        }
+
+        if !self.registrations.is_empty() {
+            let registrations: Vec<String> = self
+                .registrations
+                .iter()
+                .map(|(kind, count)| format!("{count} {kind}"))
+                .collect();
+            write!(f, ": {}", registrations.join(", "))?;
+        } else if self.registered > 0 {
+            write!(f, ": {} registered", self.registered)?;
+        }
+
+        for warning in &self.warnings {
+            write!(f, "\n       warning: {warning}")?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, "\n       error: {error}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "json")]
+fn millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(id: &str, position: Option<usize>) -> PluginReport {
+        PluginReport {
+            id: id.to_string(),
+            version: "1.0.0".to_string(),
+            origin: "static".to_string(),
+            path: None,
+            position,
+            duration: Duration::from_millis(2),
+            registrations: BTreeMap::new(),
+            registered: 0,
+            warnings: Vec::new(),
+            error: None,
+        }
+    }
+
+    fn report() -> LoadReport {
+        let counted = PluginReport {
+            registrations: BTreeMap::from([("item".to_string(), 2)]),
+            registered: 2,
+            ..plugin("counted", Some(0))
+        };
+        let uncounted = PluginReport {
+            registered: 3,
+            ..plugin("uncounted", Some(1))
+        };
+        let failed = PluginReport {
+            warnings: vec!["registered nothing".to_string()],
+            error: Some("boom".to_string()),
+            ..plugin("failed", Some(2))
+        };
+        LoadReport {
+            plugins: vec![counted, uncounted, failed, plugin("skipped", None)],
+            errors: vec!["cycle".to_string()],
+        }
+    }
+
+    #[test]
+    fn counts_registrations_without_kinds() {
+        let report = report();
+        assert_eq!(report.plugins[0].registered(), 2);
+        assert_eq!(report.plugins[1].registered(), 3);
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn prints_a_table() {
+        assert_eq!(
+            report().to_string(),
+            "  1. counted 1.0.0 (static) in 2.00ms: 2 item
+  2. uncounted 1.0.0 (static) in 2.00ms: 3 registered
+  3. failed 1.0.0 (static) failed after 2.00ms
+       warning: registered nothing
+       error: boom
+   - skipped 1.0.0 (static) not loaded
+error: cycle
+"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn serializes_to_json() -> crate::Result<()> {
+        let json: serde_json::Value = serde_json::from_str(&report().to_json())?;
+        let counted = &json["plugins"][0];
+        assert_eq!(counted["id"], "counted");
+        assert_eq!(counted["position"], 0);
+        assert_eq!(counted["duration_ms"], 2.0);
+        assert_eq!(counted["registrations"]["item"], 2);
+        assert_eq!(counted["registered"], 2);
+        assert_eq!(json["plugins"][2]["error"], "boom");
+        assert_eq!(json["plugins"][3]["position"], serde_json::Value::Null);
+        assert_eq!(json["errors"][0], "cycle");
+        Ok(())
+    }
+}
@@ -0,0 +1,266 @@
+//! Plugins written as Rhai scripts.
+//!
+//! A script plugin defines `id()`, `author()` and `load()` functions, and
+//! optionally `version()`, the [`Metadata`] functions `name()`,
+//! `description()`, `license()`, `homepage()`, `tags()`, `icon()`,
+//! `locales()` and `capabilities()`, and `assets()`, which returns a map of
+//! asset names to files. Inside `load()` it calls `register(kind, table)`,
+//! and every table is turned into the host's registrable through
+//! [`FromScript`]:
+//!
+//! ```rhai
+//! fn id() { "cookie-scripts" }
+//! fn author() { "designer" }
+//!
+//! fn load() {
+//!     register("upgrade", #{ name: "Butter Churn", stage: 2 });
+//! }
+//! ```
+
+use crate::asset::Asset;
+use crate::{Context, Metadata, Plugin, Result};
+use rhai::{AST, Array, Engine, Map, Scope};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub use rhai;
+
+/// Host-supplied conversion from a script table into a registrable.
+pub trait FromScript: Sized {
+    fn from_script(kind: &str, table: Map) -> Result<Self>;
+}
+
+pub struct ScriptPlugin {
+    ast: AST,
+    author: String,
+    id: String,
+    version: String,
+    metadata: Metadata,
+}
+
+impl ScriptPlugin {
+    /// Loads the script at `path`. Relative `icon()`, `locales()` and
+    /// `assets()` paths are resolved against the script's directory.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        let mut plugin = Self::from_source(&source)?;
+        if let Some(dir) = path.parent() {
+            let metadata = &mut plugin.metadata;
+            for path in [&mut metadata.icon, &mut metadata.locales]
+                .into_iter()
+                .flatten()
+                .chain(metadata.assets.values_mut())
+            {
+                *path = dir.join(&path);
+            }
+        }
+        Ok(plugin)
+    }
+
+    pub fn from_source(source: &str) -> Result<Self> {
+        let engine = Engine::new();
+        let ast = engine.compile(source)?;
+        let id = engine.call_fn::<String>(&mut Scope::new(), &ast, "id", ())?;
+        let author = engine.call_fn::<String>(&mut Scope::new(), &ast, "author", ())?;
+        let optional = |name: &str| -> Result<Option<String>> {
+            match ast.iter_functions().any(|f| f.name == name) {
+                true => Ok(Some(engine.call_fn(&mut Scope::new(), &ast, name, ())?)),
+                false => Ok(None),
+            }
+        };
+
+        let list = |name: &str| -> Result<Vec<String>> {
+            if !ast.iter_functions().any(|f| f.name == name) {
+                return Ok(Vec::new());
+            }
+            let items = engine.call_fn::<Array>(&mut Scope::new(), &ast, name, ())?;
+            Ok(items
+                .into_iter()
+                .map(|item| item.into_string())
+                .collect::<std::result::Result<_, _>>()?)
+        };
+
+        let map = |name: &str| -> Result<BTreeMap<String, PathBuf>> {
+            if !ast.iter_functions().any(|f| f.name == name) {
+                return Ok(BTreeMap::new());
+            }
+            let items = engine.call_fn::<Map>(&mut Scope::new(), &ast, name, ())?;
+            items
+                .into_iter()
+                .map(|(key, value)| Ok((key.to_string(), value.into_string()?.into())))
+                .collect()
+        };
+
+        let version = optional("version")?.unwrap_or_else(|| "0.0.0".to_string());
+        let metadata = Metadata {
+            name: optional("name")?,
+            description: optional("description")?,
+            license: optional("license")?,
+            homepage: optional("homepage")?,
+            tags: list("tags")?,
+            icon: optional("icon")?.map(Into::into),
+            locales: optional("locales")?.map(Into::into),
+            assets: map("assets")?,
+            capabilities: list("capabilities")?,
+        };
+
+        Ok(Self {
+            ast,
+            author,
+            id,
+            version,
+            metadata,
+        })
+    }
+}
+
+impl<C> Plugin<C> for ScriptPlugin
+where
+    C: Context,
+    C::Registrable: FromScript,
+{
+    fn author(&self) -> &str {
+        &self.author
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Borrowed(&self.metadata)
+    }
+
+    fn assets(&self) -> Vec<(&str, Asset)> {
+        self.metadata.file_assets()
+    }
+
+    fn load(&self, ctx: &mut C) -> Result<()> {
+        let registrations = Arc::new(Mutex::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        let sink = registrations.clone();
+        engine.register_fn("register", move |kind: &str, table: Map| {
+            sink.lock().unwrap().push((kind.to_string(), table));
+        });
+        engine.call_fn::<()>(&mut Scope::new(), &self.ast, "load", ())?;
+
+        let registrations = std::mem::take(&mut *registrations.lock().unwrap());
+        for (kind, table) in registrations {
+            ctx.register(C::Registrable::from_script(&kind, table)?);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestContext;
+
+    const SCRIPT: &str = r#"
+        fn id() { "cookie-scripts" }
+        fn author() { "designer" }
+        fn version() { "1.2.0" }
+        fn name() { "Cookie Scripts" }
+        fn tags() { ["upgrades", "idle"] }
+        fn locales() { "locales" }
+        fn assets() { #{ cookie: "icons/cookie.png" } }
+
+        fn load() {
+            register("item", #{ name: "Butter Churn", level: 2 });
+            register("item", #{ name: "Sugar Mill" });
+        }
+    "#;
+
+    #[test]
+    fn reads_metadata() -> Result<()> {
+        let plugin = ScriptPlugin::from_source(SCRIPT)?;
+        let plugin: &dyn Plugin<TestContext> = &plugin;
+
+        assert_eq!(plugin.id(), "cookie-scripts");
+        assert_eq!(plugin.author(), "designer");
+        assert_eq!(plugin.version(), "1.2.0");
+        let metadata = plugin.metadata();
+        assert_eq!(metadata.name.as_deref(), Some("Cookie Scripts"));
+        assert_eq!(metadata.tags, ["upgrades", "idle"]);
+        assert_eq!(metadata.description, None);
+        assert!(metadata.capabilities.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn optional_functions_default() -> Result<()> {
+        let plugin = ScriptPlugin::from_source(r#"fn id() { "x" } fn author() { "y" }"#)?;
+        let plugin: &dyn Plugin<TestContext> = &plugin;
+
+        assert_eq!(plugin.version(), "0.0.0");
+        assert_eq!(*plugin.metadata(), Metadata::default());
+        assert!(plugin.assets().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn registers_tables() -> Result<()> {
+        let plugin = ScriptPlugin::from_source(SCRIPT)?;
+        let mut ctx = TestContext::default();
+        plugin.load(&mut ctx)?;
+
+        assert_eq!(ctx.names(), ["Butter Churn", "Sugar Mill"]);
+        let levels: Vec<u32> = ctx
+            .get_registrables::<crate::testing::Item>()
+            .iter()
+            .map(|item| item.level)
+            .collect();
+        assert_eq!(levels, [2, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_kinds_fail_the_load() -> Result<()> {
+        let plugin = ScriptPlugin::from_source(
+            r#"fn id() { "x" } fn author() { "y" } fn load() { register("boat", #{}); }"#,
+        )?;
+        let mut ctx = TestContext::default();
+
+        let error = plugin.load(&mut ctx).unwrap_err();
+        assert_eq!(error.to_string(), "unknown registrable kind `boat`");
+        Ok(())
+    }
+
+    #[test]
+    fn requires_id_and_author() {
+        assert!(ScriptPlugin::from_source(r#"fn id() { "x" }"#).is_err());
+        assert!(ScriptPlugin::from_source("fn id( {").is_err());
+    }
+
+    #[test]
+    fn resolves_paths_against_the_script() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("cybird-script-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("cookies.rhai");
+        std::fs::write(&path, SCRIPT)?;
+
+        let plugin = ScriptPlugin::from_file(&path);
+        std::fs::remove_dir_all(&dir)?;
+        let plugin = plugin?;
+        let plugin: &dyn Plugin<TestContext> = &plugin;
+
+        assert_eq!(plugin.metadata().locales, Some(dir.join("locales")));
+        let assets = plugin.assets();
+        assert_eq!(assets[0].0, "cookie");
+        assert_eq!(
+            assets[0].1.path(),
+            Some(dir.join("icons/cookie.png").as_path())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,397 @@
+//! Saving and restoring the mutable state of registrables.
+//!
+//! A [`Snapshot`] stores the state of every registrable that implements
+//! [`Persist`], keyed by the id of the plugin that registered it and the
+//! item's own key. Owners come from [`Context::owner`], so contexts that do
+//! not record them, and items registered by the host outside a [`Loader`],
+//! are stored under [`HOST_ID`].
+//!
+//! Each plugin's state also records the plugin version that wrote it. When a
+//! snapshot is restored after a plugin update, the plugin's [`Migration`]s
+//! are applied in sequence to bring the saved state up to date first.
+
+use crate::loader::Loader;
+use crate::{Context, Plugin, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+pub use serde_json::{Value, json};
+
+pub const HOST_ID: &str = "host";
+
+/// Implemented by a context's registrable type to take part in snapshots.
+pub trait Persist {
+    /// Identifies the item among those registered by the same plugin, or
+    /// `None` if the item has no state worth saving.
+    fn key(&self) -> Option<&str>;
+
+    fn save(&self) -> Value;
+
+    fn restore(&mut self, state: Value) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub plugins: BTreeMap<String, PluginState>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PluginState {
+    /// The plugin version that wrote this state. `None` for host items and
+    /// snapshots written before versions were recorded, which migrate as
+    /// version "0.0.0", the default [`Plugin::version`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub items: BTreeMap<String, Value>,
+}
+
+impl PluginState {
+    /// Moves the state saved under `from` to `to`, for items that were renamed.
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(state) = self.items.remove(from) {
+            self.items.insert(to.to_string(), state);
+        }
+    }
+}
+
+/// One step that turns state saved by plugin version `from` into state
+/// understood by version `to`.
+pub struct Migration {
+    pub from: String,
+    pub to: String,
+    pub apply: fn(&mut PluginState) -> Result<()>,
+}
+
+impl Migration {
+    pub fn new(
+        from: impl Into<String>,
+        to: impl Into<String>,
+        apply: fn(&mut PluginState) -> Result<()>,
+    ) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            apply,
+        }
+    }
+}
+
+/// Applies `migrations` one after another until `state` is at `version`.
+///
+/// State without a recorded version is taken to be at version "0.0.0". On
+/// error `state` is unchanged.
+pub fn migrate(state: &mut PluginState, version: &str, migrations: &[Migration]) -> Result<()> {
+    let mut migrated = state.clone();
+    let mut current = state.version.clone().unwrap_or_else(|| "0.0.0".to_string());
+    let mut steps = 0;
+    while current != version {
+        let Some(migration) = migrations.iter().find(|m| m.from == current) else {
+            return Err(format!("no migration from version {current} to {version}").into());
+        };
+        steps += 1;
+        if steps > migrations.len() {
+            return Err(format!("migrations from version {current} never reach {version}").into());
+        }
+
+        (migration.apply)(&mut migrated).map_err(|error| {
+            format!(
+                "migration from {} to {} failed: {error}",
+                migration.from, migration.to
+            )
+        })?;
+        current = migration.to.clone();
+    }
+
+    migrated.version = Some(current);
+    *state = migrated;
+    Ok(())
+}
+
+/// Migrates a JSON-encoded [`PluginState`] with `plugin`'s migrations and
+/// returns the JSON-encoded outcome, as a `Result<PluginState, String>`.
+///
+/// This is how native plugins run their migrations on behalf of the host.
+pub fn migrate_json<C: Context>(plugin: &dyn Plugin<C>, state: &str) -> String {
+    let result = serde_json::from_str::<PluginState>(state)
+        .map_err(|error| error.to_string())
+        .and_then(|mut state| match plugin.migrate(&mut state) {
+            Ok(()) => Ok(state),
+            Err(error) => Err(error.to_string()),
+        });
+    serde_json::to_string(&result).expect("plugin state serializes to JSON")
+}
+
+/// What happened to each item during [`Snapshot::restore`], as
+/// `(plugin id, item key)` pairs.
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    pub restored: Vec<(String, String)>,
+    /// Saved items that no longer exist in the context.
+    pub missing: Vec<(String, String)>,
+    /// Items in the context without any saved state.
+    pub fresh: Vec<(String, String)>,
+    /// Items whose saved state could not be applied, or whose key an earlier
+    /// item of the same plugin already has.
+    pub failed: Vec<(String, String, String)>,
+    /// Plugins whose saved state could not be migrated to their current
+    /// version, with the reason. None of their saved items are restored.
+    pub unmigratable: Vec<(String, String)>,
+}
+
+impl Snapshot {
+    /// Saves the state of every item with a key, under the plugin that
+    /// registered it according to [`Context::owner`].
+    ///
+    /// Fails if one plugin registered two items with the same key, as their
+    /// states would overwrite each other.
+    pub fn capture<C>(ctx: &C, loader: &Loader<C>) -> Result<Self>
+    where
+        C: Context,
+        C::Registrable: Persist,
+    {
+        let mut snapshot = Snapshot::default();
+        for (index, registrable) in ctx.registrables().iter().enumerate() {
+            let Some(key) = registrable.key() else {
+                continue;
+            };
+
+            let owner = ctx.owner(index).unwrap_or(HOST_ID);
+            let state = snapshot
+                .plugins
+                .entry(owner.to_string())
+                .or_insert_with(|| PluginState {
+                    version: loader
+                        .plugin(owner)
+                        .map(|plugin| plugin.version().to_string()),
+                    items: BTreeMap::new(),
+                });
+            if state
+                .items
+                .insert(key.to_string(), registrable.save())
+                .is_some()
+            {
+                return Err(format!("`{owner}` registered more than one item `{key}`").into());
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Migrates the saved state of each loaded plugin to its current version
+    /// and applies it to the matching items in `ctx`.
+    ///
+    /// Items that cannot be matched, migrated or restored are listed in the
+    /// report rather than failing the whole restore.
+    pub fn restore<C>(&self, ctx: &mut C, loader: &Loader<C>) -> RestoreReport
+    where
+        C: Context,
+        C::Registrable: Persist,
+    {
+        let mut report = RestoreReport::default();
+        let mut seen = HashSet::new();
+
+        let mut plugins = self.plugins.clone();
+        plugins.retain(|id, state| {
+            let Some(plugin) = loader.plugin(id) else {
+                return true;
+            };
+            match plugin.migrate(state) {
+                Ok(()) => true,
+                Err(error) => {
+                    report.unmigratable.push((id.clone(), error.to_string()));
+                    false
+                }
+            }
+        });
+
+        let owners: Vec<String> = (0..ctx.registrables().len())
+            .map(|index| ctx.owner(index).unwrap_or(HOST_ID).to_string())
+            .collect();
+        for (registrable, owner) in ctx.registrables_mut().iter_mut().zip(owners) {
+            let Some(key) = registrable.key().map(str::to_string) else {
+                continue;
+            };
+            if !seen.insert((owner.clone(), key.clone())) {
+                let error = "another item has the same key".to_string();
+                report.failed.push((owner, key, error));
+                continue;
+            }
+
+            let state = plugins
+                .get(&owner)
+                .and_then(|plugin| plugin.items.get(&key));
+            match state {
+                Some(state) => match registrable.restore(state.clone()) {
+                    Ok(()) => report.restored.push((owner, key)),
+                    Err(error) => report.failed.push((owner, key, error.to_string())),
+                },
+                None => report.fresh.push((owner, key)),
+            }
+        }
+
+        for (owner, plugin) in &plugins {
+            for key in plugin.items.keys() {
+                let entry = (owner.clone(), key.clone());
+                if !seen.contains(&entry) {
+                    report.missing.push(entry);
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Migrates the state saved under `id` to `version`, for state the host
+    /// registered itself under [`HOST_ID`]. The host records its own version
+    /// by setting [`PluginState::version`] before saving.
+    pub fn migrate(&mut self, id: &str, version: &str, migrations: &[Migration]) -> Result<()> {
+        match self.plugins.get_mut(id) {
+            Some(state) => migrate(state, version, migrations),
+            None => Ok(()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Item, TestContext, TestPlugin};
+
+    fn loaded(plugins: Vec<TestPlugin>) -> Result<(TestContext, Loader<TestContext>)> {
+        let mut ctx = TestContext::default();
+        let mut loader = Loader::new();
+        for plugin in plugins {
+            loader.add(plugin);
+        }
+        loader.load(&mut ctx)?;
+        Ok((ctx, loader))
+    }
+
+    fn levels(ctx: &TestContext) -> Vec<u32> {
+        ctx.get_registrables::<Item>()
+            .into_iter()
+            .map(|item| item.level)
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_through_a_file() -> Result<()> {
+        let plugins = vec![
+            TestPlugin::new("a").registers("shared").registers("a"),
+            TestPlugin::new("b").registers("shared"),
+        ];
+        let (mut ctx, loader) = loaded(plugins.clone())?;
+        ctx.register(Item::new("host"));
+        for (level, item) in ctx.get_registrables_mut::<Item>().into_iter().enumerate() {
+            item.level = level as u32 + 1;
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("cybird-snapshot-{}.json", std::process::id()));
+        Snapshot::capture(&ctx, &loader)?.save(&path)?;
+        let snapshot = Snapshot::load(&path);
+        std::fs::remove_file(&path)?;
+        let snapshot = snapshot?;
+        assert_eq!(snapshot.plugins["a"].items["shared"], json!(1));
+        assert_eq!(snapshot.plugins["b"].items["shared"], json!(3));
+        assert_eq!(snapshot.plugins[HOST_ID].items["host"], json!(4));
+
+        let (mut restored, loader) = loaded(plugins)?;
+        restored.register(Item::new("host"));
+        let report = snapshot.restore(&mut restored, &loader);
+        assert_eq!(levels(&restored), [1, 2, 3, 4]);
+        assert_eq!(report.restored.len(), 4);
+        assert!(report.missing.is_empty() && report.fresh.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reports_missing_and_fresh_items() -> Result<()> {
+        let (ctx, loader) = loaded(vec![TestPlugin::new("a").registers("old")])?;
+        let snapshot = Snapshot::capture(&ctx, &loader)?;
+
+        let (mut ctx, loader) = loaded(vec![TestPlugin::new("a").registers("new")])?;
+        let report = snapshot.restore(&mut ctx, &loader);
+        assert_eq!(report.missing, [("a".to_string(), "old".to_string())]);
+        assert_eq!(report.fresh, [("a".to_string(), "new".to_string())]);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() -> Result<()> {
+        let (mut ctx, loader) = loaded(vec![TestPlugin::new("a").registers("x").registers("x")])?;
+        let error = Snapshot::capture(&ctx, &loader).unwrap_err();
+        assert_eq!(error.to_string(), "`a` registered more than one item `x`");
+
+        let mut snapshot = Snapshot::default();
+        snapshot.plugins.insert(
+            "a".to_string(),
+            PluginState {
+                version: None,
+                items: BTreeMap::from([("x".to_string(), json!(7))]),
+            },
+        );
+        let report = snapshot.restore(&mut ctx, &loader);
+        assert_eq!(levels(&ctx), [7, 0]);
+        assert_eq!(report.restored.len(), 1);
+        assert_eq!(report.failed.len(), 1);
+        Ok(())
+    }
+
+    fn add_level(state: &mut PluginState) -> Result<()> {
+        for level in state.items.values_mut() {
+            *level = json!(level.as_u64().unwrap_or_default() + 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn migrates_in_sequence() -> Result<()> {
+        let migrations = [
+            Migration::new("0.2.0", "0.3.0", add_level),
+            Migration::new("0.0.0", "0.1.0", add_level),
+            Migration::new("0.1.0", "0.2.0", add_level),
+        ];
+        let mut state = PluginState {
+            version: None,
+            items: BTreeMap::from([("x".to_string(), json!(1))]),
+        };
+        migrate(&mut state, "0.3.0", &migrations)?;
+        assert_eq!(state.version.as_deref(), Some("0.3.0"));
+        assert_eq!(state.items["x"], json!(4));
+
+        migrate(&mut state, "0.3.0", &migrations)?;
+        assert_eq!(state.items["x"], json!(4));
+        Ok(())
+    }
+
+    #[test]
+    fn fails_without_a_path_to_the_version() {
+        let mut state = PluginState::default();
+        let error = migrate(&mut state, "1.0.0", &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "no migration from version 0.0.0 to 1.0.0"
+        );
+        assert_eq!(state, PluginState::default());
+
+        let looping = [
+            Migration::new("0.0.0", "0.1.0", add_level),
+            Migration::new("0.1.0", "0.0.0", add_level),
+        ];
+        let error = migrate(&mut state, "1.0.0", &looping).unwrap_err();
+        assert!(error.to_string().contains("never reach 1.0.0"));
+        assert_eq!(state, PluginState::default());
+    }
+}
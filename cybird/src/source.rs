@@ -0,0 +1,320 @@
+//! Where plugins come from.
+//!
+//! A [`PluginSource`] opens plugins of one kind (compiled in, native
+//! libraries, data directories or scripts) as [`BoxedPlugin`]s. Added to a
+//! [`Loader`](crate::loader::Loader), they are ordered, loaded and reported on
+//! the same way whatever their origin.
+
+use crate::loader::BoxedPlugin;
+use crate::native::NativePlugin;
+use crate::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "trust")]
+use {crate::trust::Trust, std::sync::Arc};
+
+/// Where a plugin was opened from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// Compiled into the host, either handed to the loader directly or found
+    /// in the static registry.
+    Static,
+    Native(PathBuf),
+    /// A native library run in a helper process.
+    Process(PathBuf),
+    Data(PathBuf),
+    Script(PathBuf),
+}
+
+impl Origin {
+    /// The file or directory the plugin was opened from.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Origin::Static => None,
+            Origin::Native(path)
+            | Origin::Process(path)
+            | Origin::Data(path)
+            | Origin::Script(path) => Some(path),
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Static => write!(f, "static"),
+            Origin::Native(path) => write!(f, "native library {}", path.display()),
+            Origin::Process(path) => write!(f, "helper process for {}", path.display()),
+            Origin::Data(path) => write!(f, "data plugin {}", path.display()),
+            Origin::Script(path) => write!(f, "script {}", path.display()),
+        }
+    }
+}
+
+/// A plugin that could not be opened.
+#[derive(Debug)]
+pub struct SourceError {
+    pub origin: Origin,
+    pub error: Box<dyn std::error::Error>,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.origin, self.error)
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+pub trait PluginSource<C: Context> {
+    /// Opens every plugin the source provides. Plugins are opened one by one,
+    /// so a broken plugin does not hide the others.
+    fn open(&self) -> Vec<(Origin, Result<BoxedPlugin<C>>)>;
+}
+
+/// Every plugin in the static registry for the context.
+pub struct StaticSource;
+
+impl<C: Context + 'static> PluginSource<C> for StaticSource {
+    fn open(&self) -> Vec<(Origin, Result<BoxedPlugin<C>>)> {
+        crate::static_plugins::<C>()
+            .into_iter()
+            .map(|plugin| (Origin::Static, Ok(plugin)))
+            .collect()
+    }
+}
+
+/// A native plugin library, or a directory of them.
+pub struct NativeSource {
+    path: PathBuf,
+    #[cfg(feature = "trust")]
+    trust: Option<Arc<Trust>>,
+}
+
+impl NativeSource {
+    /// # Safety
+    ///
+    /// Every library found is opened with [`NativePlugin::open_all`], whose
+    /// requirements apply to each of them. Only the plugins built for the
+    /// context are added.
+    pub unsafe fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            #[cfg(feature = "trust")]
+            trust: None,
+        }
+    }
+
+    /// Checks every library against `trust` before opening it.
+    #[cfg(feature = "trust")]
+    pub fn with_trust(mut self, trust: Arc<Trust>) -> Self {
+        self.trust = Some(trust);
+        self
+    }
+}
+
+impl<C: Context> PluginSource<C> for NativeSource {
+    fn open(&self) -> Vec<(Origin, Result<BoxedPlugin<C>>)> {
+        open_each_many(&self.path, is_library, Origin::Native, |path| {
+            #[cfg(feature = "trust")]
+            if let Some(trust) = &self.trust {
+                let plugins = unsafe { NativePlugin::open_verified(trust.verify(path)?)? };
+                return Ok(for_context(plugins));
+            }
+            let plugins = unsafe { NativePlugin::open_all(path)? };
+            Ok(for_context(plugins))
+        })
+    }
+}
+
+/// The plugins built for the context `C`.
+fn for_context<C: Context>(plugins: Vec<NativePlugin>) -> Vec<BoxedPlugin<C>> {
+    plugins
+        .into_iter()
+        .filter(|plugin| plugin.is_for::<C>())
+        .map(|plugin| Box::new(plugin) as BoxedPlugin<C>)
+        .collect()
+}
+
+/// Like [`NativeSource`], but every library runs in a helper process. See
+/// [`process`](crate::process).
+#[cfg(feature = "process")]
+pub struct ProcessSource {
+    path: PathBuf,
+    #[cfg(feature = "trust")]
+    trust: Option<Arc<Trust>>,
+}
+
+#[cfg(feature = "process")]
+impl ProcessSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            #[cfg(feature = "trust")]
+            trust: None,
+        }
+    }
+
+    /// Checks every library against `trust` before opening it.
+    #[cfg(feature = "trust")]
+    pub fn with_trust(mut self, trust: Arc<Trust>) -> Self {
+        self.trust = Some(trust);
+        self
+    }
+}
+
+#[cfg(feature = "process")]
+impl<C> PluginSource<C> for ProcessSource
+where
+    C: Context,
+    C::Registrable: serde::de::DeserializeOwned,
+{
+    fn open(&self) -> Vec<(Origin, Result<BoxedPlugin<C>>)> {
+        open_each_many(&self.path, is_library, Origin::Process, |path| {
+            use crate::process::ProcessPlugin;
+
+            let boxed = |plugins: Vec<ProcessPlugin>| {
+                plugins
+                    .into_iter()
+                    .map(|plugin| Box::new(plugin) as BoxedPlugin<C>)
+                    .collect()
+            };
+            #[cfg(feature = "trust")]
+            if let Some(trust) = &self.trust {
+                return Ok(boxed(ProcessPlugin::open_verified(trust.verify(path)?)?));
+            }
+            Ok(boxed(ProcessPlugin::open_all(path)?))
+        })
+    }
+}
+
+/// A data plugin directory, or a directory of them.
+#[cfg(feature = "data")]
+pub struct DataSource {
+    path: PathBuf,
+}
+
+#[cfg(feature = "data")]
+impl DataSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(feature = "data")]
+impl<C> PluginSource<C> for DataSource
+where
+    C: Context,
+    C::Registrable: serde::de::DeserializeOwned,
+{
+    fn open(&self) -> Vec<(Origin, Result<BoxedPlugin<C>>)> {
+        use crate::data::DataPlugin;
+
+        let open =
+            |path: &Path| -> Result<BoxedPlugin<C>> { Ok(Box::new(DataPlugin::open(path)?)) };
+        if DataPlugin::is_data_plugin(&self.path) {
+            let origin = Origin::Data(self.path.clone());
+            return vec![(origin, open(&self.path))];
+        }
+        open_each(
+            &self.path,
+            |path| DataPlugin::is_data_plugin(path),
+            Origin::Data,
+            open,
+        )
+    }
+}
+
+/// A Rhai script plugin, or a directory of `.rhai` files.
+#[cfg(feature = "script")]
+pub struct ScriptSource {
+    path: PathBuf,
+}
+
+#[cfg(feature = "script")]
+impl ScriptSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(feature = "script")]
+impl<C> PluginSource<C> for ScriptSource
+where
+    C: Context,
+    C::Registrable: crate::script::FromScript,
+{
+    fn open(&self) -> Vec<(Origin, Result<BoxedPlugin<C>>)> {
+        let is_script = |path: &Path| {
+            path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("rhai")
+        };
+
+        open_each(&self.path, is_script, Origin::Script, |path| {
+            Ok(Box::new(crate::script::ScriptPlugin::from_file(path)?))
+        })
+    }
+}
+
+fn is_library(path: &Path) -> bool {
+    path.is_file()
+        && path.extension().and_then(|ext| ext.to_str()) == Some(std::env::consts::DLL_EXTENSION)
+}
+
+/// Opens `path` itself, or each entry of the directory at `path` that
+/// `matches`, in file name order.
+#[cfg(any(feature = "data", feature = "script"))]
+fn open_each<C, M, F>(
+    path: &Path,
+    matches: M,
+    origin: fn(PathBuf) -> Origin,
+    open: F,
+) -> Vec<(Origin, Result<BoxedPlugin<C>>)>
+where
+    C: Context,
+    M: Fn(&Path) -> bool,
+    F: Fn(&Path) -> Result<BoxedPlugin<C>>,
+{
+    open_each_many(path, matches, origin, |path| Ok(vec![open(path)?]))
+}
+
+/// Like [`open_each`], for files that may each hold several plugins.
+fn open_each_many<C, M, F>(
+    path: &Path,
+    matches: M,
+    origin: fn(PathBuf) -> Origin,
+    open: F,
+) -> Vec<(Origin, Result<BoxedPlugin<C>>)>
+where
+    C: Context,
+    M: Fn(&Path) -> bool,
+    F: Fn(&Path) -> Result<Vec<BoxedPlugin<C>>>,
+{
+    let paths = if path.is_dir() {
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(error) => return vec![(origin(path.to_path_buf()), Err(error.into()))],
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| matches(path))
+            .collect();
+        paths.sort();
+        paths
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut opened = Vec::new();
+    for path in paths {
+        match open(&path) {
+            Ok(plugins) => {
+                for plugin in plugins {
+                    opened.push((origin(path.clone()), Ok(plugin)));
+                }
+            }
+            Err(error) => opened.push((origin(path), Err(error))),
+        }
+    }
+    opened
+}
@@ -0,0 +1,160 @@
+//! A small context shared by the crate's unit tests.
+
+use crate::Metadata;
+use crate::capability::Kind;
+use crate::prelude::*;
+use std::borrow::Cow;
+
+#[derive(Debug, Default, Clone, PartialEq, Context)]
+pub struct TestContext(Vec<Registrable>, Owners);
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    any(feature = "data", feature = "process"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum Registrable {
+    Item(Item),
+}
+
+#[derive(Debug, Clone, PartialEq, Registrable)]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(
+    any(feature = "data", feature = "process"),
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Item {
+    pub name: String,
+    pub level: u32,
+}
+
+impl Item {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            level: 0,
+        }
+    }
+}
+
+impl Kind for Registrable {
+    const KINDS: &'static [&'static str] = &["item"];
+
+    fn kind(&self) -> &str {
+        match self {
+            Registrable::Item(_) => "item",
+        }
+    }
+}
+
+impl TestContext {
+    /// The names of every registered item, in registration order.
+    pub fn names(&self) -> Vec<&str> {
+        self.get_registrables::<Item>()
+            .into_iter()
+            .map(|item| item.name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(feature = "script")]
+impl crate::script::FromScript for Registrable {
+    fn from_script(kind: &str, table: rhai::Map) -> Result<Self> {
+        if kind != "item" {
+            return Err(format!("unknown registrable kind `{kind}`").into());
+        }
+        let name = table.get("name").ok_or("missing name")?;
+        let level = table.get("level").map_or(Ok(0), |level| level.as_int())?;
+        Ok(Registrable::Item(Item {
+            name: name.clone().into_string()?,
+            level: u32::try_from(level)?,
+        }))
+    }
+}
+
+/// A plugin that registers an item per name, or fails as told.
+#[derive(Debug, Clone, Default)]
+pub struct TestPlugin {
+    pub id: &'static str,
+    pub dependencies: Vec<&'static str>,
+    pub items: Vec<&'static str>,
+    pub capabilities: Vec<&'static str>,
+    pub fail: bool,
+}
+
+impl TestPlugin {
+    pub fn new(id: &'static str) -> Self {
+        Self {
+            id,
+            ..Self::default()
+        }
+    }
+
+    pub fn depends_on(mut self, id: &'static str) -> Self {
+        self.dependencies.push(id);
+        self
+    }
+
+    pub fn registers(mut self, name: &'static str) -> Self {
+        self.items.push(name);
+        self
+    }
+
+    pub fn declares(mut self, capability: &'static str) -> Self {
+        self.capabilities.push(capability);
+        self
+    }
+}
+
+impl Plugin<TestContext> for TestPlugin {
+    fn author(&self) -> &str {
+        "tests"
+    }
+
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    fn dependencies(&self) -> Vec<&str> {
+        self.dependencies.clone()
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Owned(Metadata {
+            capabilities: self.capabilities.iter().map(|c| c.to_string()).collect(),
+            ..Metadata::default()
+        })
+    }
+
+    fn load(&self, ctx: &mut TestContext) -> Result<()> {
+        for name in &self.items {
+            ctx.register(Item::new(name));
+        }
+        match self.fail {
+            true => Err(format!("{} failed", self.id).into()),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl crate::snapshot::Persist for Registrable {
+    fn key(&self) -> Option<&str> {
+        match self {
+            Registrable::Item(item) => Some(&item.name),
+        }
+    }
+
+    fn save(&self) -> crate::snapshot::Value {
+        match self {
+            Registrable::Item(item) => item.level.into(),
+        }
+    }
+
+    fn restore(&mut self, state: crate::snapshot::Value) -> Result<()> {
+        match self {
+            Registrable::Item(item) => item.level = serde_json::from_value(state)?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+//! Checking native libraries before they are loaded.
+//!
+//! Loading a library runs its code, so [`Trust`] compares each library's
+//! SHA-256 with the checksum file shipped next to it (`libfoo.so.sha256`, in
+//! `sha256sum` format) and with a trust list the user maintains, keyed by the
+//! library's canonical path. Libraries without a checksum file are refused
+//! unless [`Trust::allow_missing_checksums`] is set. Libraries that are
+//! unknown or changed since they were trusted are refused unless a prompt
+//! approves them, and the prompt's decision is recorded in the list.
+//!
+//! ```toml
+//! [plugins."/home/crab/game/plugins/libcrazier_crab.so"]
+//! sha256 = "9f86d0..."
+//! trusted = true
+//! decided_at = 1760000000
+//! ```
+//!
+//! [`Trust::verify`] checks a library and returns a private copy of the
+//! exact bytes it checked, so the file cannot be swapped between the check
+//! and loading it.
+
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The trust list file, keyed by canonical library path.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrustList {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, TrustEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustEntry {
+    pub sha256: String,
+    pub trusted: bool,
+    /// When the decision was made, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub decided_at: u64,
+}
+
+/// How a library compares with its checksum file and the trust list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Trusted,
+    /// The user refused this exact file before.
+    Refused,
+    /// Not on the trust list.
+    Unknown {
+        sha256: String,
+    },
+    /// On the trust list, but the file changed since it was trusted.
+    Modified {
+        trusted: String,
+        sha256: String,
+    },
+    /// The file does not match its own checksum file. Always refused.
+    Corrupt {
+        expected: String,
+        sha256: String,
+    },
+    /// There is no checksum file next to the library. Refused unless
+    /// [`Trust::allow_missing_checksums`] is set.
+    Unsigned {
+        sha256: String,
+    },
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Trusted => write!(f, "trusted"),
+            Verdict::Refused => write!(f, "refused in the trust list"),
+            Verdict::Unknown { sha256 } => write!(f, "not on the trust list (sha256 {sha256})"),
+            Verdict::Modified { trusted, sha256 } => write!(
+                f,
+                "changed since it was trusted (sha256 {sha256}, trusted {trusted})"
+            ),
+            Verdict::Corrupt { expected, sha256 } => write!(
+                f,
+                "does not match its checksum file (sha256 {sha256}, expected {expected})"
+            ),
+            Verdict::Unsigned { sha256 } => write!(f, "has no checksum file (sha256 {sha256})"),
+        }
+    }
+}
+
+type Prompt = Box<dyn Fn(&Path, &Verdict) -> bool + Send + Sync>;
+
+pub struct Trust {
+    file: PathBuf,
+    list: Mutex<TrustList>,
+    prompt: Option<Prompt>,
+    allow_missing_checksums: bool,
+}
+
+/// A private copy of a library made by [`Trust::verify`], deleted when it is
+/// dropped. Keep it alive for as long as the library is loaded.
+#[derive(Debug)]
+pub struct VerifiedLibrary {
+    original: PathBuf,
+    copy: PathBuf,
+}
+
+impl VerifiedLibrary {
+    /// The library that was checked, next to which its config file and
+    /// relative paths are looked up.
+    pub fn original(&self) -> &Path {
+        &self.original
+    }
+
+    /// The copy to load.
+    pub fn copy(&self) -> &Path {
+        &self.copy
+    }
+}
+
+impl Drop for VerifiedLibrary {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.copy);
+    }
+}
+
+impl Trust {
+    /// Reads the trust list at `file`, starting an empty one if it does not
+    /// exist yet.
+    pub fn open(file: impl Into<PathBuf>) -> Result<Self> {
+        let file = file.into();
+        let list = match std::fs::read_to_string(&file) {
+            Ok(list) => toml::from_str(&list)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => TrustList::default(),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(Self {
+            file,
+            list: Mutex::new(list),
+            prompt: None,
+            allow_missing_checksums: false,
+        })
+    }
+
+    /// Asks `prompt` whether to load libraries that are unknown or were
+    /// modified. Without a prompt they are refused.
+    pub fn with_prompt<F>(mut self, prompt: F) -> Self
+    where
+        F: Fn(&Path, &Verdict) -> bool + Send + Sync + 'static,
+    {
+        self.prompt = Some(Box::new(prompt));
+        self
+    }
+
+    /// Lets the trust list and prompt alone decide about libraries that have
+    /// no checksum file, instead of refusing them.
+    pub fn allow_missing_checksums(mut self) -> Self {
+        self.allow_missing_checksums = true;
+        self
+    }
+
+    pub fn list(&self) -> TrustList {
+        self.list.lock().unwrap().clone()
+    }
+
+    pub fn verdict(&self, library: &Path) -> Result<Verdict> {
+        self.verdict_of(library, &std::fs::read(library)?)
+    }
+
+    /// The verdict for `bytes`, read from `library`.
+    fn verdict_of(&self, library: &Path, bytes: &[u8]) -> Result<Verdict> {
+        let sha256 = sha256(bytes);
+
+        match read_checksum_file(library)? {
+            Some(expected) if !expected.eq_ignore_ascii_case(&sha256) => {
+                return Ok(Verdict::Corrupt { expected, sha256 });
+            }
+            None if !self.allow_missing_checksums => return Ok(Verdict::Unsigned { sha256 }),
+            _ => {}
+        }
+
+        let list = self.list.lock().unwrap();
+        Ok(match list.plugins.get(&key(library)) {
+            None => Verdict::Unknown { sha256 },
+            Some(entry) if entry.sha256 != sha256 && entry.trusted => Verdict::Modified {
+                trusted: entry.sha256.clone(),
+                sha256,
+            },
+            Some(entry) if entry.sha256 != sha256 => Verdict::Unknown { sha256 },
+            Some(entry) if entry.trusted => Verdict::Trusted,
+            Some(_) => Verdict::Refused,
+        })
+    }
+
+    /// Succeeds if `library` may be loaded, asking the prompt when needed and
+    /// recording its decision.
+    ///
+    /// The file is read again when it is loaded, so prefer
+    /// [`Trust::verify`], which hands back the bytes it checked.
+    pub fn check(&self, library: &Path) -> Result<()> {
+        self.check_bytes(library, &std::fs::read(library)?)
+    }
+
+    /// Checks `library` like [`Trust::check`] and copies the bytes it checked
+    /// into a private directory, to be loaded instead of `library`.
+    pub fn verify(&self, library: &Path) -> Result<VerifiedLibrary> {
+        let bytes = std::fs::read(library)?;
+        self.check_bytes(library, &bytes)?;
+        let copy = private_copy(library, &bytes)?;
+        Ok(VerifiedLibrary {
+            original: library.to_path_buf(),
+            copy,
+        })
+    }
+
+    fn check_bytes(&self, library: &Path, bytes: &[u8]) -> Result<()> {
+        let verdict = self.verdict_of(library, bytes)?;
+        let sha256 = match &verdict {
+            Verdict::Trusted => return Ok(()),
+            Verdict::Refused | Verdict::Corrupt { .. } | Verdict::Unsigned { .. } => {
+                return Err(verdict.to_string().into());
+            }
+            Verdict::Unknown { sha256 } | Verdict::Modified { sha256, .. } => sha256.clone(),
+        };
+
+        let Some(prompt) = &self.prompt else {
+            return Err(verdict.to_string().into());
+        };
+        let trusted = prompt(library, &verdict);
+        self.record(library, sha256, trusted)?;
+
+        match trusted {
+            true => Ok(()),
+            false => Err("refused when asked".into()),
+        }
+    }
+
+    /// Records a decision about the current contents of `library` and saves
+    /// the trust list.
+    pub fn decide(&self, library: &Path, trusted: bool) -> Result<()> {
+        self.record(library, sha256_file(library)?, trusted)
+    }
+
+    fn record(&self, library: &Path, sha256: String, trusted: bool) -> Result<()> {
+        let decided_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+
+        let mut list = self.list.lock().unwrap();
+        list.plugins.insert(
+            key(library),
+            TrustEntry {
+                sha256,
+                trusted,
+                decided_at,
+            },
+        );
+        std::fs::write(&self.file, toml::to_string_pretty(&*list)?)?;
+        Ok(())
+    }
+}
+
+/// The lowercase hex SHA-256 of the file at `path`.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    Ok(sha256(&std::fs::read(path)?))
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn read_checksum_file(library: &Path) -> Result<Option<String>> {
+    let mut checksum_file = library.as_os_str().to_owned();
+    checksum_file.push(".sha256");
+
+    match std::fs::read_to_string(PathBuf::from(checksum_file)) {
+        Ok(contents) => Ok(contents.split_whitespace().next().map(str::to_string)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// The trust list key of `library`, its canonical path.
+fn key(library: &Path) -> String {
+    std::fs::canonicalize(library)
+        .unwrap_or_else(|_| library.to_path_buf())
+        .display()
+        .to_string()
+}
+
+/// Writes `bytes` to a new file in a directory only this user can access,
+/// named after `library`.
+fn private_copy(library: &Path, bytes: &[u8]) -> Result<PathBuf> {
+    static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = std::env::temp_dir().join(format!("cybird-{}", std::process::id()));
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+    // A directory someone else created first is either not private or not
+    // writable by us, so creating the copy below fails
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::symlink_metadata(&dir)?;
+        if !metadata.is_dir() || metadata.permissions().mode() & 0o077 != 0 {
+            return Err(format!("{} is not a private directory", dir.display()).into());
+        }
+    }
+
+    let name = library.file_name().unwrap_or_default().to_string_lossy();
+    let copy = dir.join(format!("{}-{name}", COPIES.fetch_add(1, Ordering::Relaxed)));
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&copy)?;
+    std::io::Write::write_all(&mut file, bytes)?;
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory with a trust list, deleted when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Result<Self> {
+            let dir =
+                std::env::temp_dir().join(format!("cybird-trust-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+            Ok(Self(dir))
+        }
+
+        fn trust(&self) -> Result<Trust> {
+            Trust::open(self.0.join("trust.toml"))
+        }
+
+        /// Writes a library and, if `signed`, its checksum file.
+        fn library(&self, name: &str, bytes: &[u8], signed: bool) -> Result<PathBuf> {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, bytes)?;
+            if signed {
+                let mut checksum_file = path.clone().into_os_string();
+                checksum_file.push(".sha256");
+                std::fs::write(checksum_file, format!("{}  {name}\n", sha256(bytes)))?;
+            }
+            Ok(path)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn follows_the_trust_list() -> Result<()> {
+        let scratch = Scratch::new("list")?;
+        let trust = scratch.trust()?;
+        let library = scratch.library("libmod.so", b"one", true)?;
+
+        let sha256 = super::sha256(b"one");
+        assert_eq!(trust.verdict(&library)?, Verdict::Unknown { sha256 });
+        trust.decide(&library, true)?;
+        assert_eq!(trust.verdict(&library)?, Verdict::Trusted);
+
+        let library = scratch.library("libmod.so", b"two", true)?;
+        assert!(matches!(trust.verdict(&library)?, Verdict::Modified { .. }));
+        trust.decide(&library, false)?;
+        assert_eq!(trust.verdict(&library)?, Verdict::Refused);
+
+        let reopened = scratch.trust()?;
+        assert_eq!(reopened.verdict(&library)?, Verdict::Refused);
+        Ok(())
+    }
+
+    #[test]
+    fn keys_libraries_by_path() -> Result<()> {
+        let scratch = Scratch::new("paths")?;
+        let trust = scratch.trust()?;
+        let trusted = scratch.library("a/libmod.so", b"mod", true)?;
+        let other = scratch.library("b/libmod.so", b"mod", true)?;
+
+        trust.decide(&trusted, true)?;
+        assert_eq!(trust.verdict(&trusted)?, Verdict::Trusted);
+        assert!(matches!(trust.verdict(&other)?, Verdict::Unknown { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_corrupt_and_unsigned_libraries() -> Result<()> {
+        let scratch = Scratch::new("checksums")?;
+        let trust = scratch.trust()?.with_prompt(|_, _| true);
+        let library = scratch.library("libmod.so", b"mod", true)?;
+        std::fs::write(&library, b"tampered")?;
+        assert!(matches!(trust.verdict(&library)?, Verdict::Corrupt { .. }));
+        assert!(trust.check(&library).is_err());
+
+        let unsigned = scratch.library("libother.so", b"other", false)?;
+        assert!(matches!(
+            trust.verdict(&unsigned)?,
+            Verdict::Unsigned { .. }
+        ));
+        assert!(trust.check(&unsigned).is_err());
+
+        let trust = trust.allow_missing_checksums();
+        trust.check(&unsigned)?;
+        assert_eq!(trust.verdict(&unsigned)?, Verdict::Trusted);
+        Ok(())
+    }
+
+    #[test]
+    fn asks_the_prompt_and_records_the_answer() -> Result<()> {
+        let scratch = Scratch::new("prompt")?;
+        let library = scratch.library("libmod.so", b"mod", true)?;
+        assert!(scratch.trust()?.check(&library).is_err());
+
+        let refusing = scratch.trust()?.with_prompt(|_, _| false);
+        assert!(refusing.check(&library).is_err());
+        assert_eq!(scratch.trust()?.verdict(&library)?, Verdict::Refused);
+        Ok(())
+    }
+
+    #[test]
+    fn verifies_a_private_copy() -> Result<()> {
+        let scratch = Scratch::new("copy")?;
+        let trust = scratch.trust()?.with_prompt(|_, _| true);
+        let library = scratch.library("libmod.so", b"checked", true)?;
+
+        let verified = trust.verify(&library)?;
+        std::fs::write(&library, b"swapped")?;
+        assert_eq!(verified.original(), library);
+        assert_eq!(std::fs::read(verified.copy())?, b"checked");
+
+        let copy = verified.copy().to_path_buf();
+        drop(verified);
+        assert!(!copy.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An arithmetic expression over the upgrade level, such as `level * 100 + 1`
+/// or `level^2 + 100`.
+///
+/// Formulas support `+`, `-`, `*`, `/`, `%`, `^`, parentheses, unsigned
+/// integer constants and the `level` variable. Evaluation saturates instead of
+/// overflowing, and dividing by zero yields zero. Since formulas come from
+/// plugins, their trees may be at most [`Formula::MAX_DEPTH`] levels deep:
+/// deeper parentheses or powers are rejected as nesting too deep, and longer
+/// chains of operators, such as a sum of more terms, as too large.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "reflect",
+    derive(bevy_reflect::Reflect),
+    reflect(opaque, Clone, Debug, PartialEq)
+)]
+pub enum Formula {
+    Constant(u32),
+    Level,
+    Add(Box<Formula>, Box<Formula>),
+    Sub(Box<Formula>, Box<Formula>),
+    Mul(Box<Formula>, Box<Formula>),
+    Div(Box<Formula>, Box<Formula>),
+    Rem(Box<Formula>, Box<Formula>),
+    Pow(Box<Formula>, Box<Formula>),
+}
+
+impl Formula {
+    /// How many levels a formula's tree may have. Every parenthesis and `^`
+    /// nests one level, and every operator in a chain such as `1 + 1 + 1`
+    /// adds one, as each joins the chain so far with the next operand.
+    pub const MAX_DEPTH: usize = 64;
+
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            nesting: 0,
+        };
+        let (formula, _) = parser.expression()?;
+        match parser.peek() {
+            None => Ok(formula),
+            Some((offset, token)) => Err(ParseError::new(
+                offset,
+                format!("unexpected `{token}` after expression"),
+            )),
+        }
+    }
+
+    pub fn eval(&self, level: u32) -> u32 {
+        match self {
+            Formula::Constant(value) => *value,
+            Formula::Level => level,
+            Formula::Add(a, b) => a.eval(level).saturating_add(b.eval(level)),
+            Formula::Sub(a, b) => a.eval(level).saturating_sub(b.eval(level)),
+            Formula::Mul(a, b) => a.eval(level).saturating_mul(b.eval(level)),
+            Formula::Div(a, b) => a.eval(level).checked_div(b.eval(level)).unwrap_or(0),
+            Formula::Rem(a, b) => a.eval(level).checked_rem(b.eval(level)).unwrap_or(0),
+            Formula::Pow(a, b) => a.eval(level).saturating_pow(b.eval(level)),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Formula::Add(..) | Formula::Sub(..) => 1,
+            Formula::Mul(..) | Formula::Div(..) | Formula::Rem(..) => 2,
+            Formula::Pow(..) => 3,
+            Formula::Constant(_) | Formula::Level => 4,
+        }
+    }
+}
+
+impl From<u32> for Formula {
+    fn from(value: u32) -> Self {
+        Formula::Constant(value)
+    }
+}
+
+impl FromStr for Formula {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Formula::parse(source)
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (a, op, b) = match self {
+            Formula::Constant(value) => return write!(f, "{value}"),
+            Formula::Level => return write!(f, "level"),
+            Formula::Add(a, b) => (a, " + ", b),
+            Formula::Sub(a, b) => (a, " - ", b),
+            Formula::Mul(a, b) => (a, " * ", b),
+            Formula::Div(a, b) => (a, " / ", b),
+            Formula::Rem(a, b) => (a, " % ", b),
+            Formula::Pow(a, b) => (a, "^", b),
+        };
+
+        // `^` groups to the right, every other operator to the left.
+        let precedence = self.precedence();
+        let (left_parens, right_parens) = if matches!(self, Formula::Pow(..)) {
+            (a.precedence() <= precedence, b.precedence() < precedence)
+        } else {
+            (a.precedence() < precedence, b.precedence() <= precedence)
+        };
+
+        write_operand(f, a, left_parens)?;
+        f.write_str(op)?;
+        write_operand(f, b, right_parens)
+    }
+}
+
+fn write_operand(f: &mut fmt::Formatter<'_>, operand: &Formula, parens: bool) -> fmt::Result {
+    if parens {
+        write!(f, "({operand})")
+    } else {
+        write!(f, "{operand}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Token {
+    Number(u32),
+    Level,
+    Operator(char),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{value}"),
+            Token::Level => write!(f, "level"),
+            Token::Operator(op) => write!(f, "{op}"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(offset, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' => {
+                let mut digits = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_ascii_digit() {
+                        digits.push(c);
+                    } else if c != '_' {
+                        break;
+                    }
+                    chars.next();
+                }
+                let value = digits
+                    .parse()
+                    .map_err(|_| ParseError::new(offset, format!("invalid number `{digits}`")))?;
+                tokens.push((offset, Token::Number(value)));
+            }
+            c if c.is_alphabetic() => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !c.is_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if word != "level" {
+                    return Err(ParseError::new(
+                        offset,
+                        format!("unknown variable `{word}`"),
+                    ));
+                }
+                tokens.push((offset, Token::Level));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                tokens.push((offset, Token::Operator(c)));
+                chars.next();
+            }
+            '(' => {
+                tokens.push((offset, Token::Open));
+                chars.next();
+            }
+            ')' => {
+                tokens.push((offset, Token::Close));
+                chars.next();
+            }
+            _ => return Err(ParseError::new(offset, format!("unexpected `{c}`"))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// How many `(` and `^` the parser is inside of.
+    nesting: usize,
+}
+
+/// A formula and the depth of its tree.
+type Parsed = Result<(Formula, usize), ParseError>;
+
+impl Parser {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens
+            .get(self.position)
+            .map(|(offset, token)| (*offset, token))
+    }
+
+    fn end_offset(&self) -> usize {
+        self.tokens.last().map_or(0, |(offset, _)| offset + 1)
+    }
+
+    fn next_operator(&mut self, operators: &[char]) -> Option<(usize, char)> {
+        match self.peek() {
+            Some((offset, Token::Operator(op))) if operators.contains(op) => {
+                let op = *op;
+                self.position += 1;
+                Some((offset, op))
+            }
+            _ => None,
+        }
+    }
+
+    fn expression(&mut self) -> Parsed {
+        let mut lhs = self.term()?;
+        while let Some((offset, op)) = self.next_operator(&['+', '-']) {
+            let rhs = self.term()?;
+            lhs = match op {
+                '+' => node(offset, Formula::Add, lhs, rhs)?,
+                _ => node(offset, Formula::Sub, lhs, rhs)?,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Parsed {
+        let mut lhs = self.power()?;
+        while let Some((offset, op)) = self.next_operator(&['*', '/', '%']) {
+            let rhs = self.power()?;
+            lhs = match op {
+                '*' => node(offset, Formula::Mul, lhs, rhs)?,
+                '/' => node(offset, Formula::Div, lhs, rhs)?,
+                _ => node(offset, Formula::Rem, lhs, rhs)?,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn power(&mut self) -> Parsed {
+        let base = self.atom()?;
+        if let Some((offset, _)) = self.next_operator(&['^']) {
+            let exponent = self.nested(offset, Self::power)?;
+            return node(offset, Formula::Pow, base, exponent);
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Parsed {
+        let Some((offset, token)) = self.peek() else {
+            return Err(ParseError::new(
+                self.end_offset(),
+                "unexpected end of formula",
+            ));
+        };
+
+        let formula = match token {
+            Token::Number(value) => Formula::Constant(*value),
+            Token::Level => Formula::Level,
+            Token::Open => {
+                self.position += 1;
+                let formula = self.nested(offset, Self::expression)?;
+                return match self.peek() {
+                    Some((_, Token::Close)) => {
+                        self.position += 1;
+                        Ok(formula)
+                    }
+                    _ => Err(ParseError::new(offset, "unclosed `(`")),
+                };
+            }
+            token => return Err(ParseError::new(offset, format!("unexpected `{token}`"))),
+        };
+        self.position += 1;
+        Ok((formula, 1))
+    }
+
+    /// Parses with `parse` one level deeper, so that deeply nested input
+    /// fails instead of overflowing the stack.
+    fn nested(&mut self, offset: usize, parse: fn(&mut Self) -> Parsed) -> Parsed {
+        if self.nesting == Formula::MAX_DEPTH {
+            return Err(too_deep(offset));
+        }
+        self.nesting += 1;
+        let parsed = parse(self);
+        self.nesting -= 1;
+        parsed
+    }
+}
+
+/// Joins two operands with the operator at `offset`.
+fn node(
+    offset: usize,
+    operator: fn(Box<Formula>, Box<Formula>) -> Formula,
+    (lhs, lhs_depth): (Formula, usize),
+    (rhs, rhs_depth): (Formula, usize),
+) -> Parsed {
+    let depth = lhs_depth.max(rhs_depth) + 1;
+    if depth > Formula::MAX_DEPTH {
+        return Err(ParseError::new(
+            offset,
+            format!(
+                "formula is too large, its operators chain more than {} levels deep",
+                Formula::MAX_DEPTH
+            ),
+        ));
+    }
+    Ok((operator(Box::new(lhs), Box::new(rhs)), depth))
+}
+
+fn too_deep(offset: usize) -> ParseError {
+    ParseError::new(
+        offset,
+        format!("formula nests deeper than {} levels", Formula::MAX_DEPTH),
+    )
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Formula {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Formula {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        source.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, level: u32) -> u32 {
+        Formula::parse(source).unwrap().eval(level)
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let error = Formula::parse(source).unwrap_err();
+        (error.offset, error.message)
+    }
+
+    #[test]
+    fn respects_precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0), 7);
+        assert_eq!(eval("(1 + 2) * 3", 0), 9);
+        assert_eq!(eval("2 * 3 ^ 2", 0), 18);
+        assert_eq!(eval("level^2 + 100", 3), 109);
+        assert_eq!(eval("7 % 4 * 2", 0), 6);
+        assert_eq!(eval("level * 100 + 1", 5), 501);
+    }
+
+    #[test]
+    fn groups_left_except_powers() {
+        assert_eq!(eval("10 - 4 - 3", 0), 3);
+        assert_eq!(eval("64 / 4 / 2", 0), 8);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0), 512);
+        assert_eq!(
+            Formula::parse("2^3^2").unwrap(),
+            Formula::Pow(
+                Box::new(Formula::Constant(2)),
+                Box::new(Formula::Pow(
+                    Box::new(Formula::Constant(3)),
+                    Box::new(Formula::Constant(2))
+                )),
+            )
+        );
+    }
+
+    #[test]
+    fn saturates_and_divides_by_zero() {
+        assert_eq!(eval("1 - 2", 0), 0);
+        assert_eq!(eval("2 ^ 40", 0), u32::MAX);
+        assert_eq!(eval("5 / (level - 1)", 1), 0);
+        assert_eq!(eval("5 % 0", 0), 0);
+        assert_eq!(eval("1_000", 0), 1000);
+    }
+
+    #[test]
+    fn reports_error_offsets() {
+        assert_eq!(error(""), (0, "unexpected end of formula".into()));
+        assert_eq!(error("1 +"), (3, "unexpected end of formula".into()));
+        assert_eq!(error("1 + x"), (4, "unknown variable `x`".into()));
+        assert_eq!(error("(1 + 2"), (0, "unclosed `(`".into()));
+        assert_eq!(error("1 2"), (2, "unexpected `2` after expression".into()));
+        assert_eq!(error("1 $ 2"), (2, "unexpected `$`".into()));
+        assert_eq!(error("* 2"), (0, "unexpected `*`".into()));
+        assert_eq!(
+            error("99999999999"),
+            (0, "invalid number `99999999999`".into())
+        );
+    }
+
+    #[test]
+    fn rejects_deep_nesting_and_long_chains() {
+        let depth = Formula::MAX_DEPTH;
+        let too_deep = format!("formula nests deeper than {depth} levels");
+        let too_large =
+            format!("formula is too large, its operators chain more than {depth} levels deep");
+
+        let parens = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(error(&parens), (depth, too_deep.clone()));
+        let powers = format!("{}2", "2^".repeat(100_000));
+        assert_eq!(error(&powers).1, too_deep);
+        let sums = format!("1{}", "+1".repeat(100_000));
+        assert_eq!(error(&sums), (2 * depth - 1, too_large));
+
+        let deepest = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(eval(&deepest, 0), 1);
+        let longest = format!("1{}", "+1".repeat(depth - 1));
+        assert_eq!(eval(&longest, 0), depth as u32);
+    }
+
+    #[test]
+    fn displays_what_parses_back() {
+        let cases = [
+            ("level * 100 + 1", "level * 100 + 1"),
+            ("level * (level + 1)", "level * (level + 1)"),
+            ("(10 - 4) - 3", "10 - 4 - 3"),
+            ("10 - (4 - 3)", "10 - (4 - 3)"),
+            ("2^(3^2)", "2^3^2"),
+            ("(2^3)^2", "(2^3)^2"),
+            ("((level))", "level"),
+            ("64 / (4 / 2) % 5", "64 / (4 / 2) % 5"),
+        ];
+        for (source, displayed) in cases {
+            let formula = Formula::parse(source).unwrap();
+            assert_eq!(formula.to_string(), displayed, "{source}");
+            assert_eq!(Formula::parse(displayed).unwrap(), formula, "{source}");
+        }
+    }
+}
@@ -1,45 +1,97 @@
 use cybird::prelude::*;
 
-#[derive(Default, Context)]
-pub struct PluginContext(Vec<Registrable>);
+mod formula;
 
+pub use formula::{Formula, ParseError};
+
+#[derive(Default, Clone, Context)]
+pub struct PluginContext(Vec<Registrable>, Owners);
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
 pub enum Registrable {
     Upgrade(Upgrade),
 }
 
-#[derive(Registrable)]
+impl cybird::capability::Kind for Registrable {
+    const KINDS: &'static [&'static str] = &["upgrade"];
+
+    fn kind(&self) -> &str {
+        match self {
+            Registrable::Upgrade(_) => "upgrade",
+        }
+    }
+}
+
+#[derive(Registrable, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
 pub struct Upgrade {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub level: u32,
 
     pub stage: u32,
-    pub cost: fn(u32) -> u32,
+    pub cost: Formula,
     pub description: String,
     pub effect_type: EffectType,
 
     // Effect needs to be a bit more complex i think
     pub effects: Vec<Effect>,
+
+    /// Categories such as `click`, `idle` or `prestige`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: Vec<String>,
+
+    /// Prefix of the keys `name` and `description` are translated under.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub locale_key: Option<String>,
+
+    /// Name of the icon among the assets of the plugin that registered it.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub icon: Option<String>,
+}
+
+impl cybird::locale::Localized for Upgrade {
+    fn locale_key(&self) -> Option<&str> {
+        self.locale_key.as_deref()
+    }
+}
+
+impl Tagged for Upgrade {
+    fn tags(&self) -> Vec<&str> {
+        self.tags.iter().map(String::as_str).collect()
+    }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
 pub enum EffectType {
     Additive,
     Multiplicative,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
 pub enum EffectTrigger {
     Click,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
 pub enum EffectValue {
-    Add(fn(u32) -> u32),
-    Multiply(fn(u32) -> u32),
+    Add(Formula),
+    Multiply(Formula),
     Prestige,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "reflect", derive(bevy_reflect::Reflect))]
 pub struct Effect {
     pub trigger: EffectTrigger,
     pub value: EffectValue,
@@ -47,10 +99,10 @@ pub struct Effect {
 
 impl Effect {
     pub fn to_string(&self, level: u32) -> String {
-        let value = match self.value {
-            EffectValue::Add(f) => format!("add: {}", f(level)),
-            EffectValue::Multiply(f) => format!("multiply: {}", f(level)),
-            EffectValue::Prestige => format!("prestige"),
+        let value = match &self.value {
+            EffectValue::Add(f) => format!("add: {}", f.eval(level)),
+            EffectValue::Multiply(f) => format!("multiply: {}", f.eval(level)),
+            EffectValue::Prestige => "prestige".to_string(),
         };
         format!("trigger: {:?}, value: {:?}", self.trigger, value)
     }
@@ -64,8 +116,9 @@ impl std::fmt::Debug for Upgrade {
             .field("name", &self.name)
             .field("level", &self.level)
             .field("stage", &self.stage)
-            .field("cost", &(self.cost)(self.level))
+            .field("cost", &self.cost.eval(self.level))
             .field("effect_type", &self.effect_type)
+            .field("tags", &self.tags)
             .field(
                 "effects",
                 &self
@@ -77,3 +130,42 @@ impl std::fmt::Debug for Upgrade {
             .finish()
     }
 }
+
+#[cfg(feature = "script")]
+impl cybird::script::FromScript for Registrable {
+    fn from_script(kind: &str, table: cybird::script::rhai::Map) -> Result<Self> {
+        use cybird::script::rhai::{Dynamic, serde::from_dynamic};
+
+        match kind {
+            "upgrade" => Ok(Registrable::Upgrade(from_dynamic(&Dynamic::from_map(
+                table,
+            ))?)),
+            _ => Err(format!("unknown registrable kind `{kind}`").into()),
+        }
+    }
+}
+
+#[cfg(feature = "snapshot")]
+impl cybird::snapshot::Persist for Registrable {
+    fn key(&self) -> Option<&str> {
+        match self {
+            Registrable::Upgrade(upgrade) => Some(&upgrade.name),
+        }
+    }
+
+    fn save(&self) -> cybird::snapshot::Value {
+        match self {
+            Registrable::Upgrade(upgrade) => cybird::snapshot::json!({ "level": upgrade.level }),
+        }
+    }
+
+    fn restore(&mut self, state: cybird::snapshot::Value) -> Result<()> {
+        match self {
+            Registrable::Upgrade(upgrade) => {
+                let level = state["level"].as_u64().ok_or("missing upgrade level")?;
+                upgrade.level = u32::try_from(level)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 use clicker_plugin::*;
+use cybird::Metadata;
+use cybird::asset::Asset;
 use cybird::prelude::*;
+use cybird::tracing::debug;
+use std::borrow::Cow;
 
-#[cybird::plugin]
+#[cybird::plugin(context = PluginContext)]
 #[derive(Default)]
 pub struct CrazierCrab;
 
@@ -14,35 +18,71 @@ impl Plugin<PluginContext> for CrazierCrab {
         "crazier-crab"
     }
 
+    fn version(&self) -> &str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn metadata(&self) -> Cow<'_, Metadata> {
+        Cow::Owned(Metadata {
+            name: Some("Crazier Crab".to_string()),
+            description: Some("Late-game upgrades for crabs who have seen it all.".to_string()),
+            tags: vec!["upgrades".to_string()],
+            capabilities: vec!["upgrade".to_string()],
+            // Resolved against the library's directory, so the mod ships
+            // its `locales` directory beside the library
+            locales: Some("locales".into()),
+            ..Metadata::default()
+        })
+    }
+
+    fn assets(&self) -> Vec<(&str, Asset)> {
+        vec![
+            (
+                "crumble-decintigrator",
+                Asset::embedded(include_bytes!("../assets/crumble-decintigrator.png")),
+            ),
+            (
+                "uber-oven",
+                Asset::embedded(include_bytes!("../assets/uber-oven.png")),
+            ),
+        ]
+    }
+
     fn load(&self, ctx: &mut PluginContext) -> Result<()> {
-        println!("Loading Upgrade3 plugin...");
+        debug!("registering late-game upgrades");
         ctx.register(Upgrade {
             name: "Crumble Decintigrator".to_string(),
             level: 0,
             stage: 3,
-            cost: |level| level * 100 + 1,
+            cost: "level * 100 + 1".parse()?,
             effect_type: EffectType::Additive,
             description: "Increase cookie click yield by 100 per level.".to_string(),
             effects: vec![Effect {
                 trigger: EffectTrigger::Click,
-                value: EffectValue::Add(|level| level * 100),
+                value: EffectValue::Add("level * 100".parse()?),
             }],
+            tags: vec!["click".to_string()],
+            locale_key: Some("crazier-crab.crumble-decintigrator".to_string()),
+            icon: Some("crumble-decintigrator".to_string()),
         });
 
         ctx.register(Upgrade {
             name: "Uber Oven".to_string(),
             level: 0,
             stage: 4,
-            cost: |level| level * level + 100,
+            cost: "level^2 + 100".parse()?,
             description: "Increase cookie click yield by level^2 per level.".to_string(),
             effect_type: EffectType::Additive,
             effects: vec![Effect {
                 trigger: EffectTrigger::Click,
-                value: EffectValue::Add(|level| level * level),
+                value: EffectValue::Add("level^2".parse()?),
             }],
+            tags: vec!["click".to_string()],
+            locale_key: Some("crazier-crab.uber-oven".to_string()),
+            icon: Some("uber-oven".to_string()),
         });
 
-        println!("Loading Upgrade3 Plugin end...");
+        debug!(count = 2, "registered late-game upgrades");
         Ok(())
     }
 }
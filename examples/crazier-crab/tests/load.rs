@@ -0,0 +1,22 @@
+use clicker_plugin::{PluginContext, Upgrade};
+use crazier_crab::CrazierCrab;
+
+#[test]
+fn registers_late_game_upgrades() -> cybird::Result<()> {
+    let harness = cybird_test::load_plugin::<PluginContext, _>(CrazierCrab)?;
+
+    harness.assert_count::<Upgrade>(2);
+    assert_eq!(harness.assert_registered::<Upgrade>("Uber Oven").stage, 4);
+    assert_eq!(harness.registered_by("crazier-crab").len(), 2);
+    harness.assert_snapshot("crazier_crab");
+    Ok(())
+}
+
+#[test]
+fn library_matches_static_plugin() -> cybird::Result<()> {
+    let path = cybird_test::library_path("crazier-crab");
+    let harness = unsafe { cybird_test::load_library::<PluginContext>(path)? };
+
+    harness.assert_snapshot("crazier_crab");
+    Ok(())
+}
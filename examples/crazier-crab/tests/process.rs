@@ -0,0 +1,34 @@
+use clicker_plugin::{PluginContext, Upgrade};
+use cybird::process::{ProcessPlugin, run_helper};
+
+fn main() -> cybird::Result<()> {
+    // SAFETY: the helper is only started by the tests below, on the library
+    // built from this crate
+    unsafe { run_helper::<PluginContext>() };
+
+    loads_the_library_in_a_helper()?;
+    reports_helper_errors();
+    Ok(())
+}
+
+fn loads_the_library_in_a_helper() -> cybird::Result<()> {
+    let plugin = ProcessPlugin::open(cybird_test::library_path("crazier-crab"))?;
+    let harness = cybird_test::load_plugin::<PluginContext, _>(plugin)?;
+
+    harness.assert_count::<Upgrade>(2);
+    assert_eq!(harness.registered_by("crazier-crab").len(), 2);
+    harness.assert_snapshot("crazier_crab");
+    Ok(())
+}
+
+fn reports_helper_errors() {
+    let path = cybird_test::library_path("missing-plugin");
+    let error = ProcessPlugin::open(&path)
+        .err()
+        .expect("no library to open");
+    // The helper's own error comes back, rather than the host giving up on it
+    assert!(
+        !error.to_string().starts_with("plugin helper"),
+        "unexpected error: {error}"
+    );
+}
@@ -1,10 +1,22 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::image::{CompressedImageFormats, ImageSampler, ImageType};
 use bevy::{color::palettes::basic::*, input_focus::InputFocus, prelude::*};
 use bevy_inspector_egui::quick::ResourceInspectorPlugin;
 use bevy_inspector_egui::{bevy_egui::EguiPlugin, quick::WorldInspectorPlugin};
 use clicker_plugin::*;
 use cybird::Context;
-use std::ffi::CStr;
-use std::os::raw::c_char;
+use cybird::locale::Translations;
+use cybird::snapshot::Snapshot;
+use cybird::source::{DataSource, NativeSource};
+use cybird::trust::{Trust, Verdict};
+use cybird_bevy::prelude::*;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+const SAVE_FILE: &str = "./save.json";
+const TRUST_FILE: &str = "./plugins/trust.toml";
 
 #[derive(Resource, Default, Reflect)]
 struct Score(u32);
@@ -15,27 +27,21 @@ enum Transaction {
     Decrease(u32),
 }
 
-#[derive(Resource, Default)]
-struct PluginLoader(pub Vec<libloading::Library>);
-
-impl PluginLoader {
-    fn add(&mut self, lib: libloading::Library) {
-        self.0.push(lib);
-    }
-}
-
 #[derive(Resource, Default, Reflect)]
 struct PluginDisplay(pub Vec<String>);
 
 impl PluginDisplay {
-    fn add(&mut self, id: String) {
-        self.0.push(id);
+    fn add(&mut self, plugin: &PluginLoaded) {
+        let mut entry = format!("{} {}", plugin.name, plugin.version);
+        if let Some(description) = &plugin.description {
+            entry.push_str(&format!(" - {description}"));
+        }
+        self.0.push(entry);
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Default)]
 pub struct GameState {
-    context: PluginContext,
     stage: u32,
 }
 
@@ -57,20 +63,31 @@ struct OnUpgrade(UpgradeId);
 #[derive(Component)]
 struct CurrencyText;
 
-impl Default for GameState {
-    fn default() -> Self {
-        let map = PluginContext::default();
+/// Set when a save exists but could not be read, so it is not overwritten on
+/// exit.
+#[derive(Resource, Default)]
+struct UnreadableSave(bool);
 
-        Self {
-            context: map,
-            stage: 0,
-        }
-    }
-}
+/// Translated names, switched with the `L` key.
+#[derive(Resource, Default)]
+struct Language(Translations);
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cybird = CybirdPlugin::<PluginContext>::new();
+    if cfg!(debug_assertions) {
+        // SAFETY: the plugin is built from this workspace with the same toolchain
+        cybird = unsafe { cybird.with_path("./target/debug/upgrade3.dll") };
+    } else {
+        let trust = Trust::open(TRUST_FILE)?.with_prompt(ask_to_trust);
+        let libraries = unsafe { NativeSource::new("./plugins/") }.with_trust(Arc::new(trust));
+        cybird = cybird
+            .with_source(libraries)
+            .with_source(DataSource::new("./plugins/"));
+    }
+
     App::new()
         .add_plugins(DefaultPlugins)
+        .add_plugins(cybird)
         .add_plugins(EguiPlugin::default())
         .add_plugins(WorldInspectorPlugin::new())
         .add_plugins(ResourceInspectorPlugin::<Score>::default())
@@ -83,8 +100,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init_resource::<InputFocus>()
         .init_resource::<GameState>()
         .init_resource::<Score>()
-        .init_resource::<PluginLoader>()
         .init_resource::<PluginDisplay>()
+        .init_resource::<Language>()
+        .init_resource::<UpgradeIcons>()
+        .init_resource::<UnreadableSave>()
         .register_type::<Score>()
         .register_type::<PluginDisplay>()
         .add_systems(Startup, setup)
@@ -96,8 +115,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .add_systems(Update, button_system)
         .add_systems(Update, increase_score)
         .add_systems(Update, upgrade_button_system)
-        .add_systems(Startup, upgrade_view)
-        .add_systems(Startup, plugin_loader)
+        .add_systems(
+            Startup,
+            upgrade_view.after(LoadPlugins).after(register_upgrades),
+        )
+        .add_systems(
+            Startup,
+            restore_progress
+                .after(LoadPlugins)
+                .after(register_upgrades)
+                .before(upgrade_view),
+        )
+        .add_systems(Startup, count_kinds.before(LoadPlugins))
+        .add_systems(Startup, log_load_report.after(LoadPlugins))
+        .add_systems(Startup, load_translations.after(LoadPlugins))
+        .add_systems(
+            Startup,
+            load_upgrade_icons.after(LoadPlugins).before(upgrade_view),
+        )
+        .add_systems(Update, switch_language)
+        .add_systems(Update, update_upgrade_name)
+        .add_systems(Last, save_progress)
+        .add_systems(Update, plugin_display)
         .add_systems(Update, update_view)
         .add_systems(Update, upgrade_effect)
         .add_systems(Update, upgrade_gamestage)
@@ -108,6 +147,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Asks on the terminal before loading a plugin library that is not trusted.
+fn ask_to_trust(library: &Path, verdict: &Verdict) -> bool {
+    print!("Plugin {}: {verdict}. Load it? [y/N] ", library.display());
+    let _ = std::io::stdout().flush();
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok() && answer.trim().eq_ignore_ascii_case("y")
+}
+
 #[derive(Message)]
 struct OnStage;
 
@@ -138,34 +186,121 @@ fn stage_handler(
     }
 }
 
-fn plugin_loader(
-    mut gamestate: ResMut<GameState>,
-    mut plugin_loader: ResMut<PluginLoader>,
+fn plugin_display(
     mut plugin_display: ResMut<PluginDisplay>,
+    mut loaded: MessageReader<PluginLoaded>,
+    mut failed: MessageReader<PluginFailed>,
+    mut update_failed: MessageReader<PluginUpdateFailed>,
 ) {
-    if cfg!(debug_assertions) {
-        try_load_plugin(
-            "./target/debug/upgrade3.dll",
-            &mut gamestate,
-            &mut plugin_loader,
-            &mut plugin_display,
-        )
-        .expect("Failed to load plugin");
+    for plugin in loaded.read() {
+        info!("Loaded plugin {} by {}", plugin.id, plugin.author);
+        plugin_display.add(plugin);
+    }
+    for failure in failed.read() {
+        error!(
+            "Failed to load plugin {}: {}",
+            failure.source, failure.error
+        );
+    }
+    for failure in update_failed.read() {
+        warn!("Plugin {} failed to update: {}", failure.id, failure.error);
+    }
+}
+
+fn count_kinds(mut context: ResMut<CybirdContext<PluginContext>>) {
+    context.loader.count_kinds();
+}
+
+fn log_load_report(context: Res<CybirdContext<PluginContext>>) {
+    let report = context.loader.report();
+    if report.is_ok() {
+        info!("Plugin load report:\n{report}");
     } else {
-        try_load_plugin(
-            "./plugins/",
-            &mut gamestate,
-            &mut plugin_loader,
-            &mut plugin_display,
-        )
-        .expect("Failed to load plugin");
+        warn!("Plugin load report:\n{report}");
+    }
+}
+
+fn load_translations(mut language: ResMut<Language>, context: Res<CybirdContext<PluginContext>>) {
+    let mut translations = Translations::new("en");
+    if let Err(error) = translations.load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/locales")) {
+        warn!("Failed to read translations: {error}");
+    }
+    for error in context.loader.load_translations(&mut translations) {
+        warn!("Failed to read plugin translations: {error}");
+    }
+    language.0 = translations;
+}
+
+fn switch_language(keys: Res<ButtonInput<KeyCode>>, mut language: ResMut<Language>) {
+    if !keys.just_pressed(KeyCode::KeyL) {
+        return;
+    }
+
+    let mut locales = language.0.locales();
+    if !locales.contains(&"en") {
+        locales.push("en");
+    }
+    let current = locales
+        .iter()
+        .position(|locale| *locale == language.0.locale());
+    let next = locales[current.map_or(0, |index| (index + 1) % locales.len())].to_string();
+    info!("Switching language to {next}");
+    language.0.set_locale(next);
+}
+
+fn restore_progress(
+    mut context: ResMut<CybirdContext<PluginContext>>,
+    mut unreadable: ResMut<UnreadableSave>,
+) {
+    if !Path::new(SAVE_FILE).exists() {
+        return;
+    }
+    let snapshot = match Snapshot::load(SAVE_FILE) {
+        Ok(snapshot) => snapshot,
+        Err(error) => {
+            error!("Failed to read saved progress, it will not be overwritten: {error}");
+            unreadable.0 = true;
+            return;
+        }
+    };
+
+    let CybirdContext {
+        context, loader, ..
+    } = &mut *context;
+    let report = snapshot.restore(context, loader);
+    for (plugin, upgrade) in report.missing {
+        info!("Saved upgrade {upgrade} from {plugin} no longer exists");
+    }
+    for (plugin, upgrade, error) in report.failed {
+        warn!("Could not restore upgrade {upgrade} from {plugin}: {error}");
+    }
+    for (plugin, error) in report.unmigratable {
+        warn!("Could not migrate saved progress of {plugin}: {error}");
+    }
+}
+
+fn save_progress(
+    context: Res<CybirdContext<PluginContext>>,
+    unreadable: Res<UnreadableSave>,
+    mut exit: MessageReader<AppExit>,
+) {
+    if exit.read().next().is_none() || unreadable.0 {
+        return;
+    }
+
+    let snapshot = Snapshot::capture(&context.context, &context.loader);
+    if let Err(error) = snapshot.and_then(|snapshot| snapshot.save(SAVE_FILE)) {
+        error!("Failed to save progress: {error}");
     }
 }
 
-fn upgrade_gamestage(gamestate: Res<GameState>, mut query: Query<(&mut Visibility, &UpgradeId)>) {
+fn upgrade_gamestage(
+    gamestate: Res<GameState>,
+    context: Res<CybirdContext<PluginContext>>,
+    mut query: Query<(&mut Visibility, &UpgradeId)>,
+) {
     for (mut visibility, upgrade_id) in query.iter_mut() {
-        if let Some(Upgrade { stage, .. }) = gamestate
-            .context
+        if let Some(Upgrade { stage, .. }) = context
             .get_registrables::<Upgrade>()
             .iter()
             .find(|u| u.name == *upgrade_id.0)
@@ -176,53 +311,62 @@ fn upgrade_gamestage(gamestate: Res<GameState>, mut query: Query<(&mut Visibilit
     }
 }
 
-fn register_upgrades(mut gamestate: ResMut<GameState>) {
-    gamestate.context.register(Upgrade {
+fn register_upgrades(mut context: ResMut<CybirdContext<PluginContext>>) {
+    context.register(Upgrade {
         name: "Cookie Recycler".to_string(),
         level: 0,
 
         description: "Increase cookie click yield by 1 per level.".to_string(),
         stage: 1,
-        cost: |level| level * 2 + 1,
+        cost: "level * 2 + 1".parse().unwrap(),
         effect_type: EffectType::Additive,
         effects: vec![Effect {
             trigger: EffectTrigger::Click,
-            value: EffectValue::Add(|level| level),
+            value: EffectValue::Add("level".parse().unwrap()),
         }],
+        tags: vec!["click".to_string()],
+        locale_key: Some("crypto-crab.cookie-recycler".to_string()),
+        icon: None,
     });
 
-    gamestate.context.register(Upgrade {
+    context.register(Upgrade {
         name: "Cookie Accelerator".to_string(),
         level: 0,
 
         description: "Increase cookie click yield by 10 per level.".to_string(),
         stage: 2,
         effect_type: EffectType::Additive,
-        cost: |level| level * 10 + 10,
+        cost: "level * 10 + 10".parse().unwrap(),
         effects: vec![Effect {
             trigger: EffectTrigger::Click,
-            value: EffectValue::Add(|level| level * 10),
+            value: EffectValue::Add("level * 10".parse().unwrap()),
         }],
+        tags: vec!["click".to_string()],
+        locale_key: Some("crypto-crab.cookie-accelerator".to_string()),
+        icon: None,
     });
 
-    gamestate.context.register(Upgrade {
+    context.register(Upgrade {
         name: "Cookie Prestige".to_string(),
         level: 0,
 
         stage: 5,
-        cost: |_| 100_000,
+        cost: Formula::Constant(100_000),
         description: "Increases all yields by 2x".to_string(),
         effect_type: EffectType::Multiplicative,
         effects: vec![
             Effect {
                 trigger: EffectTrigger::Click,
-                value: EffectValue::Multiply(|level| level * 2),
+                value: EffectValue::Multiply("level * 2".parse().unwrap()),
             },
             Effect {
                 trigger: EffectTrigger::Click,
                 value: EffectValue::Prestige,
             },
         ],
+        tags: vec!["prestige".to_string()],
+        locale_key: Some("crypto-crab.cookie-prestige".to_string()),
+        icon: None,
     });
 }
 
@@ -242,16 +386,16 @@ fn score_handler(mut score: ResMut<Score>, mut message_reader: MessageReader<Tra
 }
 
 fn upgrade_effect(
-    mut gamestate: ResMut<GameState>,
+    mut context: ResMut<CybirdContext<PluginContext>>,
     score: Res<Score>,
     mut message_writer: MessageWriter<Transaction>,
     mut msg_reader: MessageReader<OnUpgrade>,
     mut prestige_writer: MessageWriter<Prestige>,
 ) {
     for msg in msg_reader.read() {
-        let upgrades = gamestate.context.get_registrables::<Upgrade>();
+        let upgrades = context.get_registrables::<Upgrade>();
         let upgrade = upgrades.iter().find(|x| x.name == msg.0.0).unwrap();
-        let cost = (upgrade.cost)(upgrade.level);
+        let cost = upgrade.cost.eval(upgrade.level);
         let upgrade_effect = upgrade
             .effects
             .iter()
@@ -263,8 +407,7 @@ fn upgrade_effect(
         }
         if score.0 >= cost {
             message_writer.write(Transaction::Decrease(cost));
-            gamestate
-                .context
+            context
                 .get_registrables_mut::<Upgrade>()
                 .into_iter()
                 .find(|x| x.name == msg.0.0)
@@ -275,7 +418,7 @@ fn upgrade_effect(
 }
 
 fn handle_prestige(
-    mut gamestate: ResMut<GameState>,
+    mut context: ResMut<CybirdContext<PluginContext>>,
     mut message_reader: MessageReader<Prestige>,
     mut score: ResMut<Score>,
 ) {
@@ -283,7 +426,7 @@ fn handle_prestige(
         score.0 = 0;
         info!("Prestige triggered");
 
-        for upgrade in gamestate.context.get_registrables_mut::<Upgrade>() {
+        for upgrade in context.get_registrables_mut::<Upgrade>() {
             if upgrade.name == "Cookie Prestige" {
                 continue;
             }
@@ -314,34 +457,48 @@ const PRESSED_BUTTON: Color = Color::srgb(0.35, 0.75, 0.35);
 #[derive(Component)]
 struct UpgradeCost(UpgradeId);
 
-fn update_upgrade_cost(mut query: Query<(&mut Text, &UpgradeCost)>, gamestate: ResMut<GameState>) {
+#[derive(Component)]
+struct UpgradeName(UpgradeId);
+
+fn update_upgrade_name(
+    mut query: Query<(&mut Text, &UpgradeName)>,
+    context: Res<CybirdContext<PluginContext>>,
+    language: Res<Language>,
+) {
+    if !language.is_changed() {
+        return;
+    }
+
+    let upgrades = context.get_registrables::<Upgrade>();
+    for (mut text, name) in query.iter_mut() {
+        let Some(upgrade) = upgrades.iter().find(|upgrade| upgrade.name == name.0.0) else {
+            continue;
+        };
+        *text = Text::new(language.0.localize(*upgrade, "name", &upgrade.name));
+    }
+}
+
+fn update_upgrade_cost(
+    mut query: Query<(&mut Text, &UpgradeCost)>,
+    context: Res<CybirdContext<PluginContext>>,
+) {
     for (mut text, cost) in query.iter_mut() {
-        let cost = (gamestate
-            .context
-            .get_registrables::<Upgrade>()
+        let upgrades = context.get_registrables::<Upgrade>();
+        let upgrade = upgrades
             .iter()
             .find(|upgrade| upgrade.name == cost.0.0)
-            .unwrap()
-            .cost)(
-            gamestate
-                .context
-                .get_registrables()
-                .iter()
-                .find(|upgrade: &&&Upgrade| upgrade.name == cost.0.0)
-                .unwrap()
-                .level,
-        );
+            .unwrap();
+        let cost = upgrade.cost.eval(upgrade.level);
         *text = Text::new(format!("Cost: {}", cost));
     }
 }
 
 fn update_upgrade_level(
     mut query: Query<(&mut Text, &UpgradeLevel)>,
-    gamestate: ResMut<GameState>,
+    context: Res<CybirdContext<PluginContext>>,
 ) {
     for (mut text, cost) in query.iter_mut() {
-        let level = gamestate
-            .context
+        let level = context
             .get_registrables::<Upgrade>()
             .iter()
             .find(|upgrade| upgrade.name == cost.0.0)
@@ -351,7 +508,11 @@ fn update_upgrade_level(
     }
 }
 
-fn upgrade_view(mut commands: Commands, gamestate: Res<GameState>) {
+fn upgrade_view(
+    mut commands: Commands,
+    context: Res<CybirdContext<PluginContext>>,
+    icons: Res<UpgradeIcons>,
+) {
     let mut canvas = commands.spawn(Node {
         width: percent(100),
         height: percent(100),
@@ -361,22 +522,53 @@ fn upgrade_view(mut commands: Commands, gamestate: Res<GameState>) {
         ..default()
     });
 
-    let mut upgrades = gamestate
-        .context
-        .get_registrables::<Upgrade>()
+    // One group per tag, so plugins can add upgrades to existing categories
+    // or bring their own
+    let upgrades = context.query::<Upgrade>();
+    let mut groups: Vec<(String, Vec<&Upgrade>)> = upgrades
+        .tags()
         .into_iter()
-        .collect::<Vec<&Upgrade>>();
+        .map(|tag| {
+            (
+                tag.to_string(),
+                context.query::<Upgrade>().with_tag(tag).into_vec(),
+            )
+        })
+        .collect();
+    let untagged: Vec<&Upgrade> = upgrades.iter().filter(|u| u.tags.is_empty()).collect();
+    if !untagged.is_empty() {
+        groups.push(("other".to_string(), untagged));
+    }
 
-    upgrades.sort_by(|a, b| {
-        a.stage
-            .cmp(&b.stage)
-            .then((a.cost)(a.level).cmp(&(b.cost)(b.level)))
-    });
+    for (tag, mut upgrades) in groups {
+        upgrades.sort_by(|a, b| {
+            a.stage
+                .cmp(&b.stage)
+                .then(a.cost.eval(a.level).cmp(&b.cost.eval(b.level)))
+        });
 
-    for upgrade in upgrades {
-        let cost = (upgrade.cost)(upgrade.level);
         canvas.with_children(|b| {
             b.spawn((
+                Text::new(tag),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+            ));
+        });
+        spawn_upgrade_buttons(&mut canvas, upgrades, &icons);
+    }
+}
+
+fn spawn_upgrade_buttons(
+    canvas: &mut EntityCommands,
+    upgrades: Vec<&Upgrade>,
+    icons: &UpgradeIcons,
+) {
+    for upgrade in upgrades {
+        let cost = upgrade.cost.eval(upgrade.level);
+        canvas.with_children(|b| {
+            let mut button = b.spawn((
                 Visibility::Hidden,
                 UpgradeId(upgrade.name.clone()),
                 UpgradeButton,
@@ -398,6 +590,7 @@ fn upgrade_view(mut commands: Commands, gamestate: Res<GameState>) {
                     (
                         ButtonText,
                         Text::new(upgrade.name.clone()),
+                        UpgradeName(UpgradeId(upgrade.name.clone())),
                         TextFont {
                             font_size: 33.0,
                             ..default()
@@ -424,27 +617,83 @@ fn upgrade_view(mut commands: Commands, gamestate: Res<GameState>) {
                     )
                 ],
             ));
+            if let Some(icon) = icons.0.get(&upgrade.name) {
+                button.with_child((
+                    ImageNode::new(icon.clone()),
+                    Node {
+                        width: px(48),
+                        height: px(48),
+                        ..default()
+                    },
+                ));
+            }
         });
     }
 }
 
+/// Icons of plugin upgrades, by upgrade name.
+#[derive(Resource, Default)]
+struct UpgradeIcons(HashMap<String, Handle<Image>>);
+
+fn load_upgrade_icons(
+    context: Res<CybirdContext<PluginContext>>,
+    assets: Res<CybirdAssets>,
+    mut images: ResMut<Assets<Image>>,
+    mut icons: ResMut<UpgradeIcons>,
+) {
+    for upgrade in context.get_registrables::<Upgrade>() {
+        let Some(icon) = &upgrade.icon else {
+            continue;
+        };
+        // Icon names are looked up among the assets of the plugin that
+        // registered the upgrade
+        let Some(asset) = context
+            .owner_of(upgrade)
+            .and_then(|id| assets.get(id, icon))
+        else {
+            warn!("Upgrade {} has no icon asset `{icon}`", upgrade.name);
+            continue;
+        };
+
+        let extension = asset
+            .path()
+            .and_then(|path| path.extension()?.to_str())
+            .unwrap_or("png");
+        let image = asset.read().and_then(|bytes| {
+            Ok(Image::from_buffer(
+                &bytes,
+                ImageType::Extension(extension),
+                CompressedImageFormats::NONE,
+                true,
+                ImageSampler::Default,
+                RenderAssetUsages::RENDER_WORLD,
+            )?)
+        });
+        match image {
+            Ok(image) => {
+                icons.0.insert(upgrade.name.clone(), images.add(image));
+            }
+            Err(error) => warn!("Failed to load icon of {}: {error}", upgrade.name),
+        }
+    }
+}
+
 #[derive(Message)]
 struct Prestige;
 
 fn increase_score(
-    gamestate: ResMut<GameState>,
+    context: Res<CybirdContext<PluginContext>>,
     mut message_reader: MessageReader<OnClick>,
     mut message_writer: MessageWriter<Transaction>,
 ) {
     for _ in message_reader.read() {
-        let base_rate = gamestate
-            .context
+        let base_rate = context
             .get_registrables::<Upgrade>()
             .iter()
             .filter(|upgrade| upgrade.effect_type == EffectType::Additive)
             .fold(1, |acc, upgrade| {
-                if let EffectValue::Add(f) = upgrade.effects[0].value {
-                    acc + f(upgrade.level)
+                if let EffectValue::Add(f) = &upgrade.effects[0].value {
+                    acc + f.eval(upgrade.level)
                 } else {
                     acc
                 }
@@ -453,23 +702,21 @@ fn increase_score(
         info!("Base rate: {}", base_rate);
         info!(
             "Upgrades: {:?}",
-            gamestate
-                .context
+            context
                 .get_registrables::<Upgrade>()
                 .iter()
                 .collect::<Vec<_>>()
         );
 
         let mut rate = base_rate as f32
-            * gamestate
-                .context
+            * context
                 .get_registrables::<Upgrade>()
                 .iter()
                 .filter(|upgrade| upgrade.effect_type == EffectType::Multiplicative)
                 .fold(1., |acc, upgrade| {
                     info!("Upgrade: {:?}", upgrade);
-                    if let EffectValue::Multiply(f) = upgrade.effects[0].value {
-                        acc * f(upgrade.level) as f32
+                    if let EffectValue::Multiply(f) = &upgrade.effects[0].value {
+                        acc * f.eval(upgrade.level) as f32
                     } else {
                         acc
                     }
@@ -639,110 +886,3 @@ fn button() -> impl Bundle {
         )],
     )
 }
-
-#[cfg(debug_assertions)]
-fn try_load_plugin(
-    path: &str,
-    gamestate: &mut GameState,
-    plugin_loader: &mut PluginLoader,
-    plugin_display: &mut PluginDisplay,
-) -> Result<(), Box<dyn std::error::Error>> {
-    unsafe {
-        println!("  Loading DLL: {}", path);
-        let lib = libloading::Library::new(path)?;
-        println!("  DLL loaded successfully");
-
-        // Get plugin metadata
-        let get_author: libloading::Symbol<unsafe extern "C" fn() -> *const c_char> =
-            lib.get(b"get_author")?;
-        let get_id: libloading::Symbol<unsafe extern "C" fn() -> *const c_char> =
-            lib.get(b"get_id")?;
-        let load_plugin: libloading::Symbol<unsafe extern "C" fn(*mut std::ffi::c_void) -> i32> =
-            lib.get(b"load_plugin")
-                .expect("Failed to load load_plugin symbol");
-        let free_string: libloading::Symbol<unsafe extern "C" fn(*mut c_char)> =
-            lib.get(b"free_string")?;
-
-        // Call the functions
-        let author_ptr = get_author();
-        let id_ptr = get_id();
-
-        let author = CStr::from_ptr(author_ptr).to_str()?;
-        let id = CStr::from_ptr(id_ptr).to_str()?;
-
-        println!("  Plugin Author: {}", author);
-        println!("  Plugin ID: {}", id);
-
-        let result =
-            load_plugin(&mut gamestate.context as *mut PluginContext as *mut std::ffi::c_void);
-        println!("  Plugin load result: {}", result);
-
-        // Free the allocated strings
-        free_string(author_ptr as *mut c_char);
-        free_string(id_ptr as *mut c_char);
-        plugin_loader.add(lib);
-        plugin_display.add(id.to_string());
-
-        Ok(())
-    }
-}
-
-#[cfg(not(debug_assertions))]
-fn try_load_plugin(
-    path: &str,
-    gamestate: &mut GameState,
-    plugin_loader: &mut PluginLoader,
-    plugin_display: &mut PluginDisplay,
-) -> Result<(), Box<dyn std::error::Error>> {
-    unsafe {
-        println!("Path: {}", path);
-        for dll in std::fs::read_dir(path)?.filter_map(|entry| entry.ok()) {
-            println!("dll: {:?}", dll);
-            if let Ok(metadata) = dll.metadata() {
-                if metadata.is_file() {
-                    unsafe {
-                        println!("  Loading DLL: {}", dll.path().display());
-                        let lib = libloading::Library::new(dll.path())?;
-                        println!("  DLL loaded successfully");
-
-                        // Get plugin metadata
-                        let get_author: libloading::Symbol<
-                            unsafe extern "C" fn() -> *const c_char,
-                        > = lib.get(b"get_author")?;
-                        let get_id: libloading::Symbol<unsafe extern "C" fn() -> *const c_char> =
-                            lib.get(b"get_id")?;
-                        let load_plugin: libloading::Symbol<
-                            unsafe extern "C" fn(*mut std::ffi::c_void) -> i32,
-                        > = lib
-                            .get(b"load_plugin")
-                            .expect("Failed to load load_plugin symbol");
-                        let free_string: libloading::Symbol<unsafe extern "C" fn(*mut c_char)> =
-                            lib.get(b"free_string")?;
-
-                        // Call the functions
-                        let author_ptr = get_author();
-                        let id_ptr = get_id();
-
-                        let author = CStr::from_ptr(author_ptr).to_str()?;
-                        let id = CStr::from_ptr(id_ptr).to_str()?;
-
-                        println!("  Plugin Author: {}", author);
-                        println!("  Plugin ID: {}", id);
-
-                        let result = load_plugin(
-                            &mut gamestate.context as *mut PluginContext as *mut std::ffi::c_void,
-                        );
-                        println!("  Plugin load result: {}", result);
-
-                        // Free the allocated strings
-                        free_string(author_ptr as *mut c_char);
-                        free_string(id_ptr as *mut c_char);
-                        plugin_loader.add(lib);
-                        plugin_display.add(id.to_string());
-                    }
-                }
-            }
-        }
-        Ok(())
-    }
-}